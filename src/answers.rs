@@ -0,0 +1,88 @@
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use crate::puzzle_set::PuzzleSet;
+use crate::validate::validate;
+
+/// The answers file as the API expects it: one line of moves per puzzle in the order of the
+/// puzzle file, with an empty line for every puzzle left unanswered. Every line ends in `\n`,
+/// so a set with no answers at all still has one line per puzzle.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AnswersFile {
+    pub answers: Vec<String>,
+}
+
+impl AnswersFile {
+    pub fn from_answers(answers: Vec<String>) -> Self {
+        Self { answers }
+    }
+
+    pub fn load(path: &str) -> io::Result<Self> {
+        Ok(Self::parse(&fs::read_to_string(path)?))
+    }
+
+    /// Reads the file back exactly as `Display` writes it, one answer per line. A last line
+    /// without its `\n` still counts.
+    pub fn parse(contents: &str) -> Self {
+        Self { answers: contents.lines().map(String::from).collect() }
+    }
+
+    /// Writes to a temporary file next to `path`, flushes it to disk and renames it over `path`,
+    /// so an interrupted write never leaves a truncated answers file behind. The temporary name
+    /// is unique, runs writing the same file at once never share one.
+    pub fn write(&self, path: &str) -> io::Result<()> {
+        let temp = format!("{}.{}.tmp", path, uuid::Uuid::new_v4());
+        let written = fs::File::create(&temp).and_then(|mut file| {
+            file.write_all(self.to_string().as_bytes())?;
+            file.sync_all()
+        });
+        match written.and_then(|_| fs::rename(&temp, path)) {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                let _ = fs::remove_file(&temp);
+                Err(e)
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.answers.len()
+    }
+
+    pub fn answered(&self) -> usize {
+        self.answers.iter().filter(|moves| !moves.is_empty()).count()
+    }
+
+    /// Pads with empty answers or drops the extra ones, so there is one line per puzzle
+    pub fn resize(&mut self, count: usize) {
+        self.answers.resize(count, String::new());
+    }
+
+    /// Takes every answer from `other` that solves its puzzle in `puzzles` in fewer moves than
+    /// the answer held here, or solves one left unanswered. Answers from `other` that do not
+    /// solve their puzzle are ignored, so a file written for another puzzle set merges nothing.
+    pub fn merge(&mut self, other: &AnswersFile, puzzles: &PuzzleSet) -> usize {
+        self.resize(self.len().max(puzzles.len()));
+        let mut taken = 0;
+
+        for (idx, moves) in other.answers.iter().enumerate().take(puzzles.len()) {
+            let current = &self.answers[idx];
+            if moves.is_empty() || (!current.is_empty() && current.len() <= moves.len()) {
+                continue;
+            }
+            let solves = puzzles.get(idx).is_some_and(|puzzle| validate(puzzle, moves).is_ok_and(|report| report.is_valid()));
+            if solves {
+                self.answers[idx] = moves.clone();
+                taken += 1;
+            }
+        }
+
+        taken
+    }
+}
+
+impl fmt::Display for AnswersFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.answers.iter().try_for_each(|moves| writeln!(f, "{}", moves))
+    }
+}
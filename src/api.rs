@@ -1,17 +1,16 @@
 use std::fs::File;
-use std::io::{Read, Write};
-use reqwest::{header, Client};
+use std::io::Write;
+use reqwest::Client;
 use reqwest::header::HeaderValue;
 use reqwest::multipart;
 use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
-pub struct SubmitBody {
-    pub questions: Vec<u8>,
-    pub answers: Vec<u8>
-}
+/// Where the MetroWeather slide puzzle API lives, `--api-url` points the client elsewhere
+pub const DEFAULT_API_URL: &str = "https://api.foresight.dev.metroweather.net/v1/recruitment/slidepuzzle";
+/// Smallest puzzle set the API will generate
+pub const MIN_PUZZLE_COUNT: u32 = 10000;
 
-#[derive(Deserialize, Serialize, Debug, Clone)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 pub struct PuzzleSubmissionResponse {
     pub response_time: String,
     pub score: f32,
@@ -25,51 +24,42 @@ pub struct PuzzleSubmissionResponse {
     pub count_right: u32,
 }
 
-pub async fn get_slide_puzzle(client: &Client, puzzle_count: u32) {
+pub async fn get_slide_puzzle(client: &Client, api_url: &str, puzzle_count: u32, path: &str) -> Result<(), Box<dyn std::error::Error>> {
 
-    assert!(puzzle_count >= 10000);
-    let url = format!("https://api.foresight.dev.metroweather.net/v1/recruitment/slidepuzzle/generate?count={}", puzzle_count);
+    if puzzle_count < MIN_PUZZLE_COUNT {
+        return Err(format!("puzzle sets have at least {} puzzles, asked for {}", MIN_PUZZLE_COUNT, puzzle_count).into());
+    }
+    let url = format!("{}/generate?count={}", api_url, puzzle_count);
 
     let mut headers = reqwest::header::HeaderMap::new();
     let accept = "*/*";
 
-    let accept_header = HeaderValue::from_str(accept).unwrap();
+    let accept_header = HeaderValue::from_str(accept)?;
     headers.insert("x-api-key", accept_header);
 
 
     let res = client.get(url)
         .headers(headers)
         .send()
-        .await
-        .unwrap();
-
+        .await?
+        .error_for_status()?;
 
-    if res.status().is_success() {
-        let body = res.text().await.unwrap();
-        let mut file = File::create("slidepuzzle.txt").unwrap();
+    let body = res.text().await?;
+    let mut file = File::create(path)?;
+    file.write_all(body.as_bytes())?;
 
-        match file.write_all(&body.as_bytes()) {
-            Ok(_)  => {
-                println!("saved puzzle to slidepuzzle.txt")
-            },
-            Err(e) => {
-                println!("{:?}", e)
-            }
-        }
+    println!("saved puzzle to {}", path);
 
-    } else {
-        println!("Error: {:?}", res.status());
-    }
+    Ok(())
 }
 
-pub async fn submit_puzzle(client: &Client, questions: &str, answers: &str) -> Result<PuzzleSubmissionResponse, Box<dyn std::error::Error>> {
-    const URL: &'static str = "https://api.foresight.dev.metroweather.net/v1/recruitment/slidepuzzle";
+pub async fn submit_puzzle(client: &Client, api_url: &str, questions: &str, answers: &str) -> Result<PuzzleSubmissionResponse, Box<dyn std::error::Error>> {
 
     let form = multipart::Form::new()
         .file("questions", questions).await?
         .file("answers", answers).await?;
 
-    let res = client.post(URL)
+    let res = client.post(api_url)
         .header("accept", "application/json")
         .multipart(form)
         .send()
@@ -0,0 +1,253 @@
+use std::fmt;
+use std::ops::{Add, AddAssign};
+use std::str::FromStr;
+use crate::puzzle::{parse_moves, Direction, PuzzleError};
+
+/// Number of moves in each direction, used both for the cost of an answer and for the contest
+/// limits on the whole submission.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MoveCounts {
+    pub up: u32,
+    pub down: u32,
+    pub left: u32,
+    pub right: u32,
+}
+
+impl MoveCounts {
+    pub fn new(up: u32, down: u32, left: u32, right: u32) -> Self {
+        Self { up, down, left, right }
+    }
+
+    /// Counts the moves of an answer, rejecting anything that is not U, D, L or R
+    pub fn from_moves(moves: &str) -> Result<Self, PuzzleError> {
+        parse_moves(moves).map(|moves| Self::from_directions(&moves))
+    }
+
+    pub fn from_directions(moves: &[Direction]) -> Self {
+        let mut counts = Self::default();
+        for dir in moves {
+            match dir {
+                Direction::UP => counts.up += 1,
+                Direction::DOWN => counts.down += 1,
+                Direction::LEFT => counts.left += 1,
+                Direction::RIGHT => counts.right += 1,
+            }
+        }
+        counts
+    }
+
+    pub fn to_array(self) -> [u32; 4] {
+        [self.up, self.down, self.left, self.right]
+    }
+
+    pub fn total(&self) -> u32 {
+        self.up + self.down + self.left + self.right
+    }
+
+    pub fn fits_within(&self, limits: &MoveCounts) -> bool {
+        self.up <= limits.up && self.down <= limits.down && self.left <= limits.left && self.right <= limits.right
+    }
+
+    pub fn checked_sub(&self, other: &MoveCounts) -> Option<MoveCounts> {
+        Some(Self {
+            up: self.up.checked_sub(other.up)?,
+            down: self.down.checked_sub(other.down)?,
+            left: self.left.checked_sub(other.left)?,
+            right: self.right.checked_sub(other.right)?,
+        })
+    }
+
+    fn weighted(&self, prices: &[f64; 4]) -> f64 {
+        self.to_array().iter().zip(prices).map(|(&count, price)| count as f64 * price).sum()
+    }
+}
+
+impl Add for MoveCounts {
+    type Output = MoveCounts;
+
+    fn add(self, other: MoveCounts) -> MoveCounts {
+        Self {
+            up: self.up + other.up,
+            down: self.down + other.down,
+            left: self.left + other.left,
+            right: self.right + other.right,
+        }
+    }
+}
+
+impl AddAssign for MoveCounts {
+    fn add_assign(&mut self, other: MoveCounts) {
+        *self = *self + other;
+    }
+}
+
+/// Parses `up,down,left,right`, the same order the submission response reports its limits in.
+impl FromStr for MoveCounts {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let counts = s.split(',')
+            .map(|count| count.trim().parse::<u32>().map_err(|_| format!("invalid move count {}", count)))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        match counts[..] {
+            [up, down, left, right] => Ok(Self::new(up, down, left, right)),
+            _ => Err(format!("expected 4 move counts, got {}", counts.len())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub moves: String,
+    pub cost: MoveCounts,
+}
+
+/// Collects candidate answers for every puzzle and picks at most one per puzzle so that as many
+/// puzzles as possible are answered without going over any of the four direction limits.
+pub struct BudgetPlanner {
+    limits: MoveCounts,
+    candidates: Vec<Vec<Candidate>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BudgetPlan {
+    pub answers: Vec<String>,
+    pub totals: MoveCounts,
+    pub limits: MoveCounts,
+    pub answerable: usize,
+}
+
+impl BudgetPlanner {
+    pub fn new(limits: MoveCounts, puzzle_count: usize) -> Self {
+        Self {
+            limits,
+            candidates: vec![vec![]; puzzle_count],
+        }
+    }
+
+    /// Adds an answer `puzzle_idx` could be given, unless it contains a character that is not a move
+    pub fn add_candidate(&mut self, puzzle_idx: usize, moves: &str) -> Result<(), PuzzleError> {
+        let cost = MoveCounts::from_moves(moves)?;
+        if puzzle_idx >= self.candidates.len() {
+            self.candidates.resize(puzzle_idx + 1, vec![]);
+        }
+
+        let candidates = &mut self.candidates[puzzle_idx];
+        if !candidates.iter().any(|c| c.moves == moves) {
+            candidates.push(Candidate { moves: moves.into(), cost });
+        }
+        Ok(())
+    }
+
+    #[cfg(test)]
+    pub fn candidates(&self, puzzle_idx: usize) -> &[Candidate] {
+        self.candidates.get(puzzle_idx).map_or(&[], |c| c.as_slice())
+    }
+
+    /// Multi-dimensional knapsack with unit values, solved with Lagrangian pricing. Each round
+    /// prices the four directions, greedily packs the cheapest answers under those prices, then
+    /// raises the price of directions whose demand exceeds their limit. The best packing seen
+    /// over all rounds is returned.
+    pub fn plan(&self) -> BudgetPlan {
+        const ROUNDS: usize = 32;
+
+        let limits = self.limits.to_array();
+        let mut prices = [1.0f64; 4];
+        let mut best = self.pack(&prices);
+
+        for _ in 0..ROUNDS {
+            let mut demand = [0u64; 4];
+            for candidates in &self.candidates {
+                if let Some(candidate) = Self::cheapest(candidates, &prices) {
+                    for (total, count) in demand.iter_mut().zip(candidate.cost.to_array()) {
+                        *total += count as u64;
+                    }
+                }
+            }
+
+            if demand.iter().zip(limits).all(|(&demand, limit)| demand <= limit as u64) {
+                break;
+            }
+
+            for ((price, demand), limit) in prices.iter_mut().zip(demand).zip(limits) {
+                let ratio = demand as f64 / limit.max(1) as f64;
+                *price *= ratio.clamp(0.5, 2.0);
+            }
+            let mean = prices.iter().sum::<f64>() / 4.;
+            prices.iter_mut().for_each(|price| *price = (*price / mean).max(1e-3));
+
+            let plan = self.pack(&prices);
+            if plan.is_better_than(&best) {
+                best = plan;
+            }
+        }
+
+        best
+    }
+
+    fn cheapest<'a>(candidates: &'a [Candidate], prices: &[f64; 4]) -> Option<&'a Candidate> {
+        candidates.iter().min_by(|a, b| a.cost.weighted(prices).total_cmp(&b.cost.weighted(prices)))
+    }
+
+    fn pack(&self, prices: &[f64; 4]) -> BudgetPlan {
+        let mut order: Vec<(usize, f64)> = self.candidates.iter()
+            .enumerate()
+            .filter_map(|(idx, candidates)| Self::cheapest(candidates, prices).map(|c| (idx, c.cost.weighted(prices))))
+            .collect();
+        order.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+        let mut remaining = self.limits;
+        let mut answers = vec![String::new(); self.candidates.len()];
+        let mut totals = MoveCounts::default();
+
+        for (idx, _) in order {
+            let mut candidates: Vec<&Candidate> = self.candidates[idx].iter().collect();
+            candidates.sort_by(|a, b| a.cost.weighted(prices).total_cmp(&b.cost.weighted(prices)));
+
+            for candidate in candidates {
+                if let Some(left) = remaining.checked_sub(&candidate.cost) {
+                    remaining = left;
+                    totals += candidate.cost;
+                    answers[idx] = candidate.moves.clone();
+                    break;
+                }
+            }
+        }
+
+        BudgetPlan {
+            answers,
+            totals,
+            limits: self.limits,
+            answerable: self.candidates.iter().filter(|c| !c.is_empty()).count(),
+        }
+    }
+}
+
+impl BudgetPlan {
+    pub fn answered(&self) -> usize {
+        self.answers.iter().filter(|moves| !moves.is_empty()).count()
+    }
+
+    fn is_better_than(&self, other: &BudgetPlan) -> bool {
+        (self.answered(), std::cmp::Reverse(self.totals.total())) > (other.answered(), std::cmp::Reverse(other.totals.total()))
+    }
+}
+
+impl fmt::Display for BudgetPlan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "answering {} of {} puzzles with candidates ({} total)", self.answered(), self.answerable, self.answers.len())?;
+
+        let rows = [
+            ("up", self.totals.up, self.limits.up),
+            ("down", self.totals.down, self.limits.down),
+            ("left", self.totals.left, self.limits.left),
+            ("right", self.totals.right, self.limits.right),
+        ];
+        for (name, used, limit) in rows {
+            writeln!(f, "  {:<6} {:>8} / {:<8} ({} spare)", name, used, limit, limit.saturating_sub(used))?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::Duration;
+use redis::Commands;
+use serde::{Deserialize, Serialize};
+use crate::limits::{SolveOutcome, SolveResult, SolveStats};
+use crate::puzzle::{parse_moves, serialize_moves, Puzzle, PuzzleError, SolveConfig};
+
+pub const DEFAULT_REDIS_URL: &str = "redis://127.0.0.1:6379/";
+const KEY_PREFIX: &str = "slidepuzzle:solution:";
+/// Compares and sets in one step on the server, so no other client can store a longer answer
+/// between the read and the write. Entries that do not decode are replaced.
+const PUT_IF_SHORTER: &str = r#"
+local current = redis.call('GET', KEYS[1])
+if current then
+    local ok, existing = pcall(cjson.decode, current)
+    if ok and type(existing) == 'table' and tonumber(existing.length) and tonumber(existing.length) <= tonumber(ARGV[2]) then
+        return 0
+    end
+end
+redis.call('SET', KEYS[1], ARGV[1])
+return 1
+"#;
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct CachedSolution {
+    pub moves: String,
+    pub length: usize,
+    pub solver: String,
+    pub timestamp: i64,
+}
+
+impl CachedSolution {
+    pub fn new(moves: &str, solver: &str) -> Self {
+        Self {
+            moves: moves.into(),
+            length: moves.len(),
+            solver: solver.into(),
+            timestamp: chrono::Utc::now().timestamp(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum CacheError {
+    Redis(redis::RedisError),
+    Serde(serde_json::Error),
+}
+
+impl fmt::Display for CacheError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CacheError::Redis(e) => write!(f, "Redis error: {}", e),
+            CacheError::Serde(e) => write!(f, "Malformed cache entry: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for CacheError {}
+
+impl From<redis::RedisError> for CacheError {
+    fn from(e: redis::RedisError) -> Self {
+        CacheError::Redis(e)
+    }
+}
+
+impl From<serde_json::Error> for CacheError {
+    fn from(e: serde_json::Error) -> Self {
+        CacheError::Serde(e)
+    }
+}
+
+/// Answers keyed by the canonical `Puzzle::serialized()` board, so duplicate boards and repeated
+/// runs never solve the same puzzle twice. Shared by every solver thread, so implementations
+/// handle their own locking.
+pub trait SolutionCache: Send + Sync {
+    fn get(&self, board: &str) -> Result<Option<CachedSolution>, CacheError>;
+
+    fn put(&self, board: &str, solution: &CachedSolution) -> Result<(), CacheError>;
+
+    /// Stores the solution unless a shorter one is already cached
+    fn put_if_shorter(&self, board: &str, solution: &CachedSolution) -> Result<bool, CacheError> {
+        match self.get(board)? {
+            Some(existing) if existing.length <= solution.length => Ok(false),
+            _ => self.put(board, solution).map(|_| true),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryCache {
+    solutions: Mutex<HashMap<String, CachedSolution>>,
+}
+
+impl MemoryCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl SolutionCache for MemoryCache {
+    fn get(&self, board: &str) -> Result<Option<CachedSolution>, CacheError> {
+        Ok(self.solutions.lock().unwrap().get(board).cloned())
+    }
+
+    fn put(&self, board: &str, solution: &CachedSolution) -> Result<(), CacheError> {
+        self.solutions.lock().unwrap().insert(board.into(), solution.clone());
+        Ok(())
+    }
+
+    fn put_if_shorter(&self, board: &str, solution: &CachedSolution) -> Result<bool, CacheError> {
+        let mut solutions = self.solutions.lock().unwrap();
+        match solutions.get(board) {
+            Some(existing) if existing.length <= solution.length => Ok(false),
+            _ => {
+                solutions.insert(board.into(), solution.clone());
+                Ok(true)
+            },
+        }
+    }
+}
+
+/// Cache backed by the Redis service from `docker-compose.yaml`. Entries are stored as JSON
+/// strings under `slidepuzzle:solution:<board>`.
+///
+/// Each thread takes a connection from the pool for the length of one command and puts it
+/// back afterwards, opening a new one when the pool is empty, so no lock is held while
+/// waiting on Redis.
+pub struct RedisCache {
+    client: redis::Client,
+    idle: Mutex<Vec<redis::Connection>>,
+    put_if_shorter: redis::Script,
+}
+
+impl RedisCache {
+    pub fn connect(url: &str) -> Result<Self, CacheError> {
+        let cache = Self {
+            client: redis::Client::open(url)?,
+            idle: Mutex::new(vec![]),
+            put_if_shorter: redis::Script::new(PUT_IF_SHORTER),
+        };
+        // Fails straight away when Redis is not there, rather than on the first puzzle
+        let connection = cache.open()?;
+        cache.idle.lock().unwrap().push(connection);
+        Ok(cache)
+    }
+
+    fn open(&self) -> Result<redis::Connection, CacheError> {
+        Ok(self.client.get_connection_with_timeout(Duration::from_secs(2))?)
+    }
+
+    /// Runs one command on a pooled connection. A connection that failed is dropped rather
+    /// than returned to the pool, it may have been left mid-reply.
+    fn with_connection<T>(&self, command: impl FnOnce(&mut redis::Connection) -> redis::RedisResult<T>) -> Result<T, CacheError> {
+        let pooled = self.idle.lock().unwrap().pop();
+        let mut connection = match pooled {
+            Some(connection) => connection,
+            None => self.open()?,
+        };
+        let result = command(&mut connection)?;
+        self.idle.lock().unwrap().push(connection);
+        Ok(result)
+    }
+
+    fn key(board: &str) -> String {
+        format!("{}{}", KEY_PREFIX, board)
+    }
+}
+
+impl SolutionCache for RedisCache {
+    fn get(&self, board: &str) -> Result<Option<CachedSolution>, CacheError> {
+        let value: Option<String> = self.with_connection(|connection| connection.get(Self::key(board)))?;
+        Ok(value.map(|value| serde_json::from_str(&value)).transpose()?)
+    }
+
+    fn put(&self, board: &str, solution: &CachedSolution) -> Result<(), CacheError> {
+        let value = serde_json::to_string(solution)?;
+        self.with_connection(|connection| connection.set(Self::key(board), value))
+    }
+
+    fn put_if_shorter(&self, board: &str, solution: &CachedSolution) -> Result<bool, CacheError> {
+        let value = serde_json::to_string(solution)?;
+        let stored: i32 = self.with_connection(|connection| {
+            self.put_if_shorter.key(Self::key(board)).arg(value).arg(solution.length).invoke(connection)
+        })?;
+        Ok(stored == 1)
+    }
+}
+
+/// Returns the cached answer for the board if there is one, otherwise solves it and caches the
+/// answer. A cached answer comes back with empty stats. Cache failures are reported but never
+/// stop the solve.
+pub fn solve_cached(
+    cache: &dyn SolutionCache,
+    puzzle: &mut Puzzle,
+    config: &SolveConfig,
+) -> Result<SolveResult, PuzzleError> {
+    let board = puzzle.serialized();
+
+    match cache.get(&board).map(|solution| solution.map(|solution| parse_moves(&solution.moves))) {
+        Ok(Some(Ok(moves))) => return Ok(SolveResult { outcome: SolveOutcome::Solved(moves), stats: SolveStats::default() }),
+        Ok(Some(Err(e))) => eprintln!("ignoring the cached answer for {}: {}", board, e),
+        Ok(None) => {},
+        Err(e) => eprintln!("cache lookup failed for {}: {}", board, e),
+    }
+
+    let result = puzzle.search(config)?;
+
+    // A stopped solve's best answer is still a valid one
+    if let Some(moves) = result.outcome.answer() {
+        let solution = CachedSolution::new(&serialize_moves(moves), &config.name());
+        if let Err(e) = cache.put_if_shorter(&board, &solution) {
+            eprintln!("could not cache {}: {}", board, e);
+        }
+    }
+
+    Ok(result)
+}
@@ -0,0 +1,244 @@
+use std::fmt;
+use std::time::Duration;
+use crate::api::{DEFAULT_API_URL, MIN_PUZZLE_COUNT};
+use crate::budget::MoveCounts;
+use crate::cache::DEFAULT_REDIS_URL;
+use crate::heuristic::HeuristicKind;
+use crate::limits::SolveLimits;
+use crate::optimise::DEFAULT_WINDOW;
+use crate::pattern_db::DEFAULT_PDB_DIR;
+use crate::puzzle::{Algorithm, SolveConfig};
+
+pub const USAGE: &str = "\
+usage: slidePuzzleSolver <command> [options]
+
+commands:
+  fetch [count]     download a new puzzle set of at least 10000 puzzles (default 10000)
+  solve             solve every puzzle in the input file and write the answers
+  submit            upload the input and answers files for grading
+  status            summarise how many puzzles the answers file covers
+  validate          replay every answer on its puzzle and report the ones that do not solve it
+  plan              choose which answers to submit without exceeding the move limits
+  optimise          shorten the answers in the answers file, each still solving its puzzle
+  show <index>      print a single puzzle from the input file
+
+options:
+  -i, --input <file>        puzzle file (default slidepuzzle.txt)
+  -o, --output <file>       answers file (default answers.txt)
+  -t, --threshold <f>       heuristic threshold used to prune the search (default 80, none for
+                            wastar and ara)
+  -a, --algorithm <name>    search algorithm, astar, ida, bidirectional, wastar (weighted A*),
+                            ara (anytime, improves its answer until done or out of time) or
+                            reduction (solves a line at a time, fast on any board) (default astar)
+  -w, --weight <w>          weight on the heuristic for wastar, and the starting weight for ara
+                            (default 2 for wastar, 3 for ara)
+      --time-limit <secs>   time each puzzle may take, ara settles for its best answer so far
+      --node-limit <n>      nodes each puzzle may expand
+      --state-limit <n>     states each puzzle may hold in memory at once
+      --batch-time-limit <secs>
+                            time the whole solve may take, puzzles still running are stopped and
+                            the answers found so far saved (Ctrl-C does the same)
+  -H, --heuristic <name>    heuristic used by the search (default legacy for astar, manhattan otherwise):
+                              manhattan, linear-conflict, pdb, walking-distance, walking-distance-walls,
+                              legacy, max(a,b,..) or sum(a,b,..)
+      --window <n>          longest stretch of moves optimise tries to replace (default 12)
+      --pdb-dir <dir>       where pattern databases are saved and loaded (default pdb)
+  -j, --threads <n>         number of solver threads (default: all cores)
+  -l, --limits <u,d,l,r>    override the up, down, left and right move limits from the puzzle file
+  -c, --candidates <file>   extra answers file to draw candidates from when planning, repeatable
+      --api-url <url>       puzzle API to fetch from and submit to (default the MetroWeather API)
+      --redis <url>         solution cache (default redis://127.0.0.1:6379/)
+      --no-cache            solve every puzzle without consulting the solution cache
+  -h, --help                print this message";
+
+pub const DEFAULT_INPUT: &str = "slidepuzzle.txt";
+pub const DEFAULT_OUTPUT: &str = "answers.txt";
+pub const DEFAULT_THRESHOLD: f32 = 80.;
+pub const DEFAULT_PUZZLE_COUNT: u32 = 10000;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Fetch { count: u32 },
+    Solve,
+    Submit,
+    Status,
+    Validate,
+    Plan,
+    Optimise,
+    Show { index: usize },
+    Help,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    pub input: String,
+    pub output: String,
+    pub heuristic_threshold: Option<f32>,
+    pub algorithm: Algorithm,
+    pub heuristic: Option<HeuristicKind>,
+    pub weight: Option<f32>,
+    pub solve_limits: SolveLimits,
+    pub batch_time_limit: Option<Duration>,
+    pub window: usize,
+    pub pdb_dir: String,
+    pub threads: Option<usize>,
+    pub limits: Option<MoveCounts>,
+    pub candidates: Vec<String>,
+    pub api_url: String,
+    pub redis: String,
+    pub no_cache: bool,
+}
+
+impl Options {
+    pub fn solve_config(&self) -> SolveConfig {
+        let mut config = SolveConfig::new(self.algorithm);
+        if let Some(heuristic) = &self.heuristic {
+            config.heuristic = heuristic.clone();
+        }
+        if let Some(weight) = self.weight {
+            config.weight = weight;
+        }
+        // Weighted and anytime searches go well past 80 on larger boards, so only an explicit
+        // threshold prunes them
+        match self.heuristic_threshold {
+            Some(threshold) => config.heuristic_threshold = threshold,
+            None if !self.algorithm.is_weighted() => config.heuristic_threshold = DEFAULT_THRESHOLD,
+            None => {},
+        }
+        config.limits = self.solve_limits.clone();
+        config.pdb_dir = Some(self.pdb_dir.clone().into());
+        config
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            input: DEFAULT_INPUT.into(),
+            output: DEFAULT_OUTPUT.into(),
+            heuristic_threshold: None,
+            algorithm: Algorithm::AStar,
+            heuristic: None,
+            weight: None,
+            solve_limits: SolveLimits::default(),
+            batch_time_limit: None,
+            window: DEFAULT_WINDOW,
+            pdb_dir: DEFAULT_PDB_DIR.into(),
+            threads: None,
+            limits: None,
+            candidates: vec![],
+            api_url: DEFAULT_API_URL.into(),
+            redis: DEFAULT_REDIS_URL.into(),
+            no_cache: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cli {
+    pub command: Command,
+    pub options: Options,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CliError {
+    MissingCommand,
+    UnknownCommand(String),
+    UnknownFlag(String),
+    MissingValue(String),
+    InvalidValue(String, String),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CliError::MissingCommand => write!(f, "No command given"),
+            CliError::UnknownCommand(cmd) => write!(f, "Unknown command: {}", cmd),
+            CliError::UnknownFlag(flag) => write!(f, "Unknown option: {}", flag),
+            CliError::MissingValue(name) => write!(f, "Missing value for {}", name),
+            CliError::InvalidValue(name, value) => write!(f, "Invalid value for {}: {}", name, value),
+        }
+    }
+}
+
+impl std::error::Error for CliError {}
+
+fn parse_value<T: std::str::FromStr>(name: &str, value: Option<String>) -> Result<T, CliError> {
+    let value = value.ok_or_else(|| CliError::MissingValue(name.into()))?;
+    value.parse().map_err(|_| CliError::InvalidValue(name.into(), value))
+}
+
+fn parse_seconds(name: &str, value: Option<String>) -> Result<Duration, CliError> {
+    let seconds: f64 = parse_value(name, value)?;
+    Duration::try_from_secs_f64(seconds).map_err(|_| CliError::InvalidValue(name.into(), seconds.to_string()))
+}
+
+impl Cli {
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, CliError> {
+        let mut options = Options::default();
+        let mut positional: Vec<String> = vec![];
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-i" | "--input" => options.input = parse_value(&arg, args.next())?,
+                "-o" | "--output" => options.output = parse_value(&arg, args.next())?,
+                "-t" | "--threshold" => options.heuristic_threshold = Some(parse_value(&arg, args.next())?),
+                "-a" | "--algorithm" => options.algorithm = parse_value(&arg, args.next())?,
+                "-H" | "--heuristic" => options.heuristic = Some(parse_value(&arg, args.next())?),
+                "-w" | "--weight" => options.weight = Some(parse_value(&arg, args.next())?),
+                "--time-limit" => options.solve_limits.time_limit = Some(parse_seconds(&arg, args.next())?),
+                "--batch-time-limit" => options.batch_time_limit = Some(parse_seconds(&arg, args.next())?),
+                "--node-limit" => options.solve_limits.max_expanded = Some(parse_value(&arg, args.next())?),
+                "--state-limit" => options.solve_limits.max_stored = Some(parse_value(&arg, args.next())?),
+                "--window" => options.window = parse_value(&arg, args.next())?,
+                "--pdb-dir" => options.pdb_dir = parse_value(&arg, args.next())?,
+                "-j" | "--threads" => {
+                    let threads: usize = parse_value(&arg, args.next())?;
+                    if threads == 0 {
+                        return Err(CliError::InvalidValue(arg, "0".into()));
+                    }
+                    options.threads = Some(threads);
+                },
+                "-l" | "--limits" => options.limits = Some(parse_value(&arg, args.next())?),
+                "-c" | "--candidates" => options.candidates.push(parse_value(&arg, args.next())?),
+                "--api-url" => options.api_url = parse_value(&arg, args.next())?,
+                "--redis" => options.redis = parse_value(&arg, args.next())?,
+                "--no-cache" => options.no_cache = true,
+                "-h" | "--help" => positional.insert(0, "help".into()),
+                flag if flag.starts_with('-') => return Err(CliError::UnknownFlag(flag.into())),
+                _ => positional.push(arg),
+            }
+        }
+
+        let mut positional = positional.into_iter();
+        let command = match positional.next().as_deref() {
+            None => return Err(CliError::MissingCommand),
+            Some("help") => Command::Help,
+            Some("fetch") => {
+                let count = match positional.next() {
+                    Some(count) => parse_value("count", Some(count))?,
+                    None => DEFAULT_PUZZLE_COUNT,
+                };
+                if count < MIN_PUZZLE_COUNT {
+                    return Err(CliError::InvalidValue("count".into(), count.to_string()));
+                }
+                Command::Fetch { count }
+            },
+            Some("solve") => Command::Solve,
+            Some("submit") => Command::Submit,
+            Some("status") => Command::Status,
+            Some("validate") => Command::Validate,
+            Some("plan") => Command::Plan,
+            Some("optimise") | Some("optimize") => Command::Optimise,
+            Some("show") => Command::Show { index: parse_value("index", positional.next())? },
+            Some(other) => return Err(CliError::UnknownCommand(other.into())),
+        };
+
+        if let Some(extra) = positional.next() {
+            return Err(CliError::UnknownCommand(extra));
+        }
+
+        Ok(Self { command, options })
+    }
+}
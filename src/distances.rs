@@ -0,0 +1,62 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use rustc_hash::FxHashMap;
+use crate::pattern_db::Layout;
+use crate::puzzle::{Direction, Puzzle};
+
+/// Distance reported between cells that no path connects, walls included. Such a tile can never
+/// reach its goal, so any estimate is a lower bound.
+pub const UNREACHABLE: u8 = u8::MAX;
+
+/// Fewest steps between every pair of cells, walking around walls. A tile needs at least this
+/// many moves to get home, so it is the per-tile distance every admissible heuristic builds on.
+/// Tables only depend on the wall pattern and are shared between boards with the same layout.
+#[derive(Debug, PartialEq)]
+pub struct CellDistances {
+    cells: usize,
+    /// `distances[from * cells + to]`
+    distances: Vec<u8>,
+}
+
+static TABLES: Mutex<Option<FxHashMap<Layout, Arc<CellDistances>>>> = Mutex::new(None);
+
+impl CellDistances {
+    /// Breadth-first search from every open cell, at most 49 searches of 49 cells each
+    pub fn build(layout: Layout) -> Self {
+        let width = layout.width as usize;
+        let cells = width * layout.height as usize;
+        let open = |idx: usize| layout.walls & 1 << idx == 0;
+
+        let mut distances = vec![UNREACHABLE; cells * cells];
+        for start in (0..cells).filter(|&idx| open(idx)) {
+            let row = &mut distances[start * cells..(start + 1) * cells];
+            row[start] = 0;
+
+            let mut queue = VecDeque::from([start]);
+            while let Some(idx) = queue.pop_front() {
+                for next in Direction::ALL.into_iter().filter_map(|dir| dir.step(idx, width, cells)) {
+                    if open(next) && row[next] == UNREACHABLE {
+                        row[next] = row[idx] + 1;
+                        queue.push_back(next);
+                    }
+                }
+            }
+        }
+
+        Self { cells, distances }
+    }
+
+    pub fn get(&self, from: usize, to: usize) -> u32 {
+        self.distances[from * self.cells + to] as u32
+    }
+}
+
+/// The table for the board's wall pattern, built the first time the layout is seen
+pub fn shared(puzzle: &Puzzle) -> Arc<CellDistances> {
+    let layout = Layout::of(puzzle);
+    TABLES.lock().unwrap()
+        .get_or_insert_with(FxHashMap::default)
+        .entry(layout)
+        .or_insert_with(|| Arc::new(CellDistances::build(layout)))
+        .clone()
+}
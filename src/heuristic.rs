@@ -0,0 +1,329 @@
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use crate::distances;
+use crate::pattern_db;
+use crate::puzzle::{column_cells, row_cells, Puzzle};
+use crate::state::{PackedState, BLANK_CODE};
+use crate::walking_distance::WalkingDistance;
+
+/// Tile ranks fit in 6 bits, see `Tile::rank`
+const RANKS: usize = 64;
+
+/// Whatever a heuristic keeps about a node so its children can be scored from the one tile that
+/// moved, see `BoardHeuristic::update`
+pub type Memo = [u128; 2];
+
+/// A heuristic prepared for one board layout. Preparation does the per-layout work (goal cells,
+/// distance tables) once so scoring stays cheap inside the search loop.
+pub trait BoardHeuristic: Send + Sync {
+    /// Scores a state from scratch and fills `memo` for `update`
+    fn evaluate(&self, state: &PackedState, memo: &mut Memo) -> u32;
+
+    /// Scores `child`, one move away from `parent`, starting from the parent's estimate. `memo`
+    /// holds the parent's memo and is updated to the child's. Heuristics that cannot do better
+    /// than starting over keep this default.
+    fn update(&self, parent: &PackedState, child: &PackedState, estimate: u32, memo: &mut Memo) -> u32 {
+        let _ = (parent, estimate);
+        self.evaluate(child, memo)
+    }
+
+    #[cfg(test)]
+    fn estimate(&self, puzzle: &Puzzle) -> u32 {
+        self.evaluate(&PackedState::from_puzzle(puzzle), &mut Memo::default())
+    }
+}
+
+/// The tile that moved between two neighbouring states, as (code, from, to)
+pub(crate) fn moved_tile(parent: &PackedState, child: &PackedState) -> (usize, usize, usize) {
+    (parent.get(child.blank()) as usize, child.blank(), parent.blank())
+}
+
+/// Heuristics that can be picked by name at runtime, e.g. `manhattan` or
+/// `max(manhattan,linear-conflict)`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum HeuristicKind {
+    /// The original `Heuristic::get_heuristic`, counts the blank and every wall on the way
+    Legacy,
+    /// Shortest walk of every tile to its goal around the walls, see `distances`
+    Manhattan,
+    /// `Manhattan` plus 2 moves for every tile that has to step out of its goal row or column
+    LinearConflict,
+    /// Disjoint additive pattern databases for the board layout, see `pattern_db`
+    PatternDatabase,
+    /// Walking distance along rows plus along columns, walls count as tiles that never move
+    WalkingDistance,
+    /// Walking distance with walls left out of the lines and crossings between lines limited to
+    /// open cells, combined with the walks around walls of `Manhattan`
+    WalkingDistanceWalls,
+    Max(Vec<HeuristicKind>),
+    Sum(Vec<HeuristicKind>),
+}
+
+impl HeuristicKind {
+    /// Whether the estimate never exceeds the true number of moves left, which optimal solvers
+    /// rely on.
+    pub fn is_admissible(&self) -> bool {
+        match self {
+            HeuristicKind::Legacy => false,
+            HeuristicKind::Manhattan
+            | HeuristicKind::LinearConflict
+            | HeuristicKind::PatternDatabase
+            | HeuristicKind::WalkingDistance
+            | HeuristicKind::WalkingDistanceWalls => true,
+            HeuristicKind::Max(kinds) => kinds.iter().all(|kind| kind.is_admissible()),
+            HeuristicKind::Sum(kinds) => kinds.len() == 1 && kinds[0].is_admissible(),
+        }
+    }
+
+    /// Sets up the heuristic for one layout, `pdb_dir` is where shared pattern databases are
+    /// saved and loaded, see `pattern_db::shared`
+    pub fn prepare(&self, solved_puzzle: &Puzzle, pdb_dir: Option<&Path>) -> Box<dyn BoardHeuristic> {
+        match self {
+            HeuristicKind::Legacy => Box::new(Legacy::new(solved_puzzle)),
+            HeuristicKind::Manhattan => Box::new(Manhattan::new(solved_puzzle)),
+            HeuristicKind::LinearConflict => Box::new(LinearConflict::new(solved_puzzle)),
+            HeuristicKind::PatternDatabase => Box::new(pattern_db::shared(solved_puzzle, pdb_dir).heuristic(solved_puzzle)),
+            HeuristicKind::WalkingDistance => Box::new(WalkingDistance::new(solved_puzzle, false)),
+            HeuristicKind::WalkingDistanceWalls => Box::new(Max(vec![
+                Box::new(WalkingDistance::new(solved_puzzle, true)),
+                Box::new(Manhattan::new(solved_puzzle)),
+            ])),
+            HeuristicKind::Max(kinds) => Box::new(Max(kinds.iter().map(|kind| kind.prepare(solved_puzzle, pdb_dir)).collect())),
+            HeuristicKind::Sum(kinds) => Box::new(Sum(kinds.iter().map(|kind| kind.prepare(solved_puzzle, pdb_dir)).collect())),
+        }
+    }
+}
+
+impl fmt::Display for HeuristicKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let list = |kinds: &[HeuristicKind]| kinds.iter().map(|kind| kind.to_string()).collect::<Vec<_>>().join(",");
+
+        match self {
+            HeuristicKind::Legacy => write!(f, "legacy"),
+            HeuristicKind::Manhattan => write!(f, "manhattan"),
+            HeuristicKind::LinearConflict => write!(f, "linear-conflict"),
+            HeuristicKind::PatternDatabase => write!(f, "pdb"),
+            HeuristicKind::WalkingDistance => write!(f, "walking-distance"),
+            HeuristicKind::WalkingDistanceWalls => write!(f, "walking-distance-walls"),
+            HeuristicKind::Max(kinds) => write!(f, "max({})", list(kinds)),
+            HeuristicKind::Sum(kinds) => write!(f, "sum({})", list(kinds)),
+        }
+    }
+}
+
+impl FromStr for HeuristicKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        let combined = |prefix: &str| -> Option<Result<Vec<HeuristicKind>, String>> {
+            let args = s.strip_prefix(prefix)?.strip_suffix(')')?;
+
+            let mut depth = 0;
+            let mut start = 0;
+            let mut kinds = vec![];
+            for (idx, char) in args.char_indices() {
+                match char {
+                    '(' => depth += 1,
+                    ')' => depth -= 1,
+                    ',' if depth == 0 => {
+                        kinds.push(args[start..idx].parse());
+                        start = idx + 1;
+                    },
+                    _ => {},
+                }
+            }
+            kinds.push(args[start..].parse());
+
+            Some(kinds.into_iter().collect())
+        };
+
+        if let Some(kinds) = combined("max(") {
+            return kinds.map(HeuristicKind::Max);
+        }
+        if let Some(kinds) = combined("sum(") {
+            return kinds.map(HeuristicKind::Sum);
+        }
+
+        match s.to_lowercase().as_str() {
+            "legacy" => Ok(HeuristicKind::Legacy),
+            "manhattan" => Ok(HeuristicKind::Manhattan),
+            "linear-conflict" | "linear_conflict" | "lc" => Ok(HeuristicKind::LinearConflict),
+            "pdb" | "pattern-database" => Ok(HeuristicKind::PatternDatabase),
+            "walking-distance" | "walking_distance" | "wd" => Ok(HeuristicKind::WalkingDistance),
+            "walking-distance-walls" | "walking_distance_walls" | "wd-walls" => Ok(HeuristicKind::WalkingDistanceWalls),
+            _ => Err(format!("unknown heuristic {:?}", s)),
+        }
+    }
+}
+
+/// The original `Heuristic::get_heuristic`: `manhattan_distance` of every tile and the blank
+/// plus the linear conflicts, scored incrementally
+struct Legacy {
+    distances: DistanceTable,
+    lines: Lines,
+}
+
+impl Legacy {
+    fn new(solved_puzzle: &Puzzle) -> Self {
+        Self {
+            distances: DistanceTable::new(solved_puzzle, true, |cell, goal| solved_puzzle.legacy_distance(cell, goal)),
+            lines: Lines::new(solved_puzzle),
+        }
+    }
+}
+
+impl BoardHeuristic for Legacy {
+    fn evaluate(&self, state: &PackedState, _: &mut Memo) -> u32 {
+        self.distances.sum(state) + 2 * self.lines.conflicts(state)
+    }
+
+    fn update(&self, parent: &PackedState, child: &PackedState, estimate: u32, _: &mut Memo) -> u32 {
+        (estimate as i32 + self.distances.delta(parent, child) + 2 * self.lines.delta(parent, child)) as u32
+    }
+}
+
+/// Distance of every tile code from every cell, so a move changes the total by two lookups
+struct DistanceTable {
+    /// `distances[cell * RANKS + code]`, 0 for walls and for the blank unless it is counted
+    distances: Vec<u32>,
+}
+
+impl DistanceTable {
+    fn new(solved_puzzle: &Puzzle, blank: bool, distance: impl Fn(usize, usize) -> u32) -> Self {
+        let mut goals = solved_puzzle.goal_cells();
+        if blank {
+            goals[BLANK_CODE as usize] = solved_puzzle.space_idx();
+        }
+
+        let cells = solved_puzzle.tiles.len();
+        let mut distances = vec![0; cells * RANKS];
+        for cell in 0..cells {
+            for (code, &goal) in goals.iter().enumerate() {
+                if goal != usize::MAX {
+                    distances[cell * RANKS + code] = distance(cell, goal);
+                }
+            }
+        }
+
+        Self { distances }
+    }
+
+    fn get(&self, cell: usize, code: usize) -> i32 {
+        self.distances[cell * RANKS + code] as i32
+    }
+
+    fn sum(&self, state: &PackedState) -> u32 {
+        (0..self.distances.len() / RANKS).map(|cell| self.distances[cell * RANKS + state.get(cell) as usize]).sum()
+    }
+
+    fn delta(&self, parent: &PackedState, child: &PackedState) -> i32 {
+        let (code, from, to) = moved_tile(parent, child);
+        let blank = BLANK_CODE as usize;
+        self.get(to, code) - self.get(from, code) + self.get(from, blank) - self.get(to, blank)
+    }
+}
+
+struct Manhattan {
+    distances: DistanceTable,
+}
+
+impl Manhattan {
+    fn new(solved_puzzle: &Puzzle) -> Self {
+        let walks = distances::shared(solved_puzzle);
+        Self { distances: DistanceTable::new(solved_puzzle, false, |cell, goal| walks.get(cell, goal)) }
+    }
+}
+
+impl BoardHeuristic for Manhattan {
+    fn evaluate(&self, state: &PackedState, _: &mut Memo) -> u32 {
+        self.distances.sum(state)
+    }
+
+    fn update(&self, parent: &PackedState, child: &PackedState, estimate: u32, _: &mut Memo) -> u32 {
+        (estimate as i32 + self.distances.delta(parent, child)) as u32
+    }
+}
+
+/// Linear conflicts of every row and column. A horizontal move leaves every row's order alone
+/// and only touches the two columns the tile moved between, and the other way round, so an
+/// update rescans two lines.
+struct Lines {
+    width: usize,
+    height: usize,
+    goals: [usize; RANKS],
+}
+
+impl Lines {
+    fn new(solved_puzzle: &Puzzle) -> Self {
+        Self { width: solved_puzzle.width as usize, height: solved_puzzle.height as usize, goals: solved_puzzle.goal_cells() }
+    }
+
+    fn conflicts(&self, state: &PackedState) -> u32 {
+        let rows: u32 = (0..self.height).map(|row| self.row(state, row)).sum();
+        let columns: u32 = (0..self.width).map(|col| self.column(state, col)).sum();
+        rows + columns
+    }
+
+    fn row(&self, state: &PackedState, row: usize) -> u32 {
+        Puzzle::line_conflicts(&row_cells(row, self.width), &self.goals, |cell| state.get(cell))
+    }
+
+    fn column(&self, state: &PackedState, col: usize) -> u32 {
+        Puzzle::line_conflicts(&column_cells(col, self.width, self.height), &self.goals, |cell| state.get(cell))
+    }
+
+    fn delta(&self, parent: &PackedState, child: &PackedState) -> i32 {
+        let (_, from, to) = moved_tile(parent, child);
+
+        let conflicts = |state: &PackedState| -> i32 {
+            let lines = if from / self.width == to / self.width {
+                self.column(state, from % self.width) + self.column(state, to % self.width)
+            } else {
+                self.row(state, from / self.width) + self.row(state, to / self.width)
+            };
+            lines as i32
+        };
+        conflicts(child) - conflicts(parent)
+    }
+}
+
+struct LinearConflict {
+    manhattan: Manhattan,
+    lines: Lines,
+}
+
+impl LinearConflict {
+    fn new(solved_puzzle: &Puzzle) -> Self {
+        Self { manhattan: Manhattan::new(solved_puzzle), lines: Lines::new(solved_puzzle) }
+    }
+}
+
+impl BoardHeuristic for LinearConflict {
+    fn evaluate(&self, state: &PackedState, memo: &mut Memo) -> u32 {
+        self.manhattan.evaluate(state, memo) + 2 * self.lines.conflicts(state)
+    }
+
+    fn update(&self, parent: &PackedState, child: &PackedState, estimate: u32, _: &mut Memo) -> u32 {
+        (estimate as i32 + self.manhattan.distances.delta(parent, child) + 2 * self.lines.delta(parent, child)) as u32
+    }
+}
+
+/// Combinations score every part from scratch, the parts' memos would not fit in one `Memo`
+struct Max(Vec<Box<dyn BoardHeuristic>>);
+
+impl BoardHeuristic for Max {
+    fn evaluate(&self, state: &PackedState, _: &mut Memo) -> u32 {
+        self.0.iter().map(|heuristic| heuristic.evaluate(state, &mut Memo::default())).max().unwrap_or(0)
+    }
+}
+
+struct Sum(Vec<Box<dyn BoardHeuristic>>);
+
+impl BoardHeuristic for Sum {
+    fn evaluate(&self, state: &PackedState, _: &mut Memo) -> u32 {
+        self.0.iter().map(|heuristic| heuristic.evaluate(state, &mut Memo::default())).sum()
+    }
+}
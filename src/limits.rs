@@ -0,0 +1,273 @@
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use crate::puzzle::Direction;
+
+/// Expansions between reads of the clock
+const CLOCK_INTERVAL: usize = 256;
+
+/// Caps on a single solve. Limits left unset never stop the search.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SolveLimits {
+    /// Wall clock time the solve may take, counted from its start
+    pub time_limit: Option<Duration>,
+    /// A point in time shared by a whole batch, whichever of this and `time_limit` comes first
+    pub deadline: Option<Instant>,
+    pub max_expanded: Option<usize>,
+    /// States held in memory at once, every node of the search tree for A*, the routes of both
+    /// sides for bidirectional search and the current path for IDA*
+    pub max_stored: Option<usize>,
+    /// Checked on every expansion, so a cancelled search stops within microseconds
+    pub cancel: Option<CancelToken>,
+}
+
+/// A flag shared between a batch and its solves. Cancelling it stops every search holding a
+/// clone at its next expansion.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl PartialEq for CancelToken {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// The limit that stopped a search
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Time,
+    Expanded,
+    Stored,
+    /// Every remaining state was cut off by the heuristic threshold
+    Threshold,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Limit::Time => write!(f, "time limit"),
+            Limit::Expanded => write!(f, "expansion limit"),
+            Limit::Stored => write!(f, "state limit"),
+            Limit::Threshold => write!(f, "heuristic threshold"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SolveOutcome {
+    Solved(Vec<Direction>),
+    /// The board can never reach the solved layout
+    Unsolvable,
+    /// Stopped by a limit, with the best answer found by then if the search keeps one
+    BudgetExhausted(Limit, Option<Vec<Direction>>),
+    /// Stopped through a `CancelToken` before it finished, with the best answer found by then if
+    /// the search keeps one
+    Cancelled(Option<Vec<Direction>>),
+}
+
+/// Why `Budget::expand` stopped a search
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Stop {
+    Limit(Limit),
+    Cancelled,
+}
+
+impl Stop {
+    /// Outcome of a search stopped here, keeping the best answer it had found
+    pub fn with_best(self, best: Option<Vec<Direction>>) -> SolveOutcome {
+        match self {
+            Stop::Limit(limit) => SolveOutcome::BudgetExhausted(limit, best),
+            Stop::Cancelled => SolveOutcome::Cancelled(best),
+        }
+    }
+}
+
+impl From<Stop> for SolveOutcome {
+    fn from(stop: Stop) -> Self {
+        stop.with_best(None)
+    }
+}
+
+impl SolveOutcome {
+    #[cfg(test)]
+    pub fn moves(self) -> Option<Vec<Direction>> {
+        match self {
+            SolveOutcome::Solved(moves) => Some(moves),
+            _ => None,
+        }
+    }
+
+    /// The answer to keep, a finished solve's or the best one a stopped solve had found
+    pub fn answer(&self) -> Option<&Vec<Direction>> {
+        match self {
+            SolveOutcome::Solved(moves) | SolveOutcome::BudgetExhausted(_, Some(moves)) | SolveOutcome::Cancelled(Some(moves)) => Some(moves),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SolveOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SolveOutcome::Solved(moves) => write!(f, "solved in {} moves", moves.len()),
+            SolveOutcome::Unsolvable => write!(f, "unsolvable"),
+            SolveOutcome::BudgetExhausted(limit, _) => write!(f, "stopped by the {}", limit),
+            SolveOutcome::Cancelled(_) => write!(f, "cancelled"),
+        }?;
+        match self {
+            SolveOutcome::BudgetExhausted(_, Some(moves)) | SolveOutcome::Cancelled(Some(moves)) => write!(f, " with an answer of {} moves", moves.len()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SolveStats {
+    pub expanded: usize,
+    /// Most states held at once
+    pub peak_stored: usize,
+    pub elapsed: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SolveResult {
+    pub outcome: SolveOutcome,
+    pub stats: SolveStats,
+}
+
+/// How the solves of a batch ended, counted by kind, and what they cost together
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BatchSummary {
+    pub solved: usize,
+    pub unsolvable: usize,
+    /// Solves stopped by each limit, in the order the limits were first hit
+    pub exhausted: Vec<(Limit, usize)>,
+    pub cancelled: usize,
+    /// Puzzles that could not be read or were never started
+    pub skipped: usize,
+    pub expanded: usize,
+    /// Most states one solve held at once
+    pub peak_stored: usize,
+    /// Time spent searching, summed over every solve
+    pub elapsed: Duration,
+}
+
+impl BatchSummary {
+    pub fn add(&mut self, result: Option<&SolveResult>) {
+        let Some(result) = result else {
+            self.skipped += 1;
+            return;
+        };
+
+        match result.outcome {
+            SolveOutcome::Solved(_) => self.solved += 1,
+            SolveOutcome::Unsolvable => self.unsolvable += 1,
+            SolveOutcome::BudgetExhausted(limit, _) => match self.exhausted.iter_mut().find(|(seen, _)| *seen == limit) {
+                Some((_, count)) => *count += 1,
+                None => self.exhausted.push((limit, 1)),
+            },
+            SolveOutcome::Cancelled(_) => self.cancelled += 1,
+        }
+        self.expanded += result.stats.expanded;
+        self.peak_stored = self.peak_stored.max(result.stats.peak_stored);
+        self.elapsed += result.stats.elapsed;
+    }
+}
+
+impl fmt::Display for BatchSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "solved {}, unsolvable {}", self.solved, self.unsolvable)?;
+        for (limit, count) in &self.exhausted {
+            write!(f, ", stopped by the {} {}", limit, count)?;
+        }
+        writeln!(f, ", cancelled {}, skipped {}", self.cancelled, self.skipped)?;
+        write!(f, "expanded {} states, at most {} stored at once, {:.1?} searching", self.expanded, self.peak_stored, self.elapsed)
+    }
+}
+
+/// One solve's progress against its limits
+pub(crate) struct Budget {
+    limits: SolveLimits,
+    started: Instant,
+    deadline: Option<Instant>,
+    stats: SolveStats,
+    pruned: bool,
+}
+
+impl Budget {
+    pub fn new(limits: SolveLimits) -> Self {
+        let started = Instant::now();
+        let deadline = match (limits.time_limit.and_then(|limit| started.checked_add(limit)), limits.deadline) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+
+        Self { limits, started, deadline, stats: SolveStats::default(), pruned: false }
+    }
+
+    /// Counts one expansion while `stored` states are held. The clock is read on the first
+    /// expansion and then every `CLOCK_INTERVAL`.
+    pub fn expand(&mut self, stored: usize) -> Result<(), Stop> {
+        self.stats.expanded += 1;
+        self.stats.peak_stored = self.stats.peak_stored.max(stored);
+
+        if self.is_cancelled() {
+            return Err(Stop::Cancelled);
+        }
+        if self.limits.max_expanded.is_some_and(|max| self.stats.expanded > max) {
+            return Err(Stop::Limit(Limit::Expanded));
+        }
+        if self.limits.max_stored.is_some_and(|max| stored > max) {
+            return Err(Stop::Limit(Limit::Stored));
+        }
+        if (self.stats.expanded - 1).is_multiple_of(CLOCK_INTERVAL) && self.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return Err(Stop::Limit(Limit::Time));
+        }
+
+        Ok(())
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.limits.cancel.as_ref().is_some_and(CancelToken::is_cancelled)
+    }
+
+    /// When this solve runs out of time, for searches run on its behalf
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Records that the heuristic threshold cut off a state
+    pub fn prune(&mut self) {
+        self.pruned = true;
+    }
+
+    /// Outcome of a search that ran out of states to try, which only proves the board
+    /// unsolvable when the threshold did not cut anything off
+    pub fn exhausted(&self) -> SolveOutcome {
+        if self.pruned {
+            SolveOutcome::BudgetExhausted(Limit::Threshold, None)
+        } else {
+            SolveOutcome::Unsolvable
+        }
+    }
+
+    pub fn finish(mut self, outcome: SolveOutcome) -> SolveResult {
+        self.stats.elapsed = self.started.elapsed();
+        SolveResult { outcome, stats: self.stats }
+    }
+}
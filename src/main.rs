@@ -0,0 +1,329 @@
+#![allow(non_snake_case)]
+#![allow(clippy::upper_case_acronyms)]
+
+mod answers;
+mod api;
+mod budget;
+mod cache;
+mod cli;
+mod distances;
+mod heuristic;
+mod limits;
+#[cfg(test)]
+mod mock_api;
+mod optimise;
+mod pattern_db;
+mod puzzle;
+mod puzzle_set;
+mod reduction;
+mod state;
+mod test;
+mod tile;
+mod traits;
+#[cfg(test)]
+mod util;
+mod validate;
+mod walking_distance;
+
+use std::process::ExitCode;
+use std::time::Instant;
+use indicatif::{ProgressBar, ProgressStyle};
+use rayon::prelude::*;
+use reqwest::Client;
+use crate::answers::AnswersFile;
+use crate::budget::{BudgetPlan, BudgetPlanner, MoveCounts};
+use crate::cache::{MemoryCache, RedisCache, SolutionCache};
+use crate::cli::{Cli, Command, Options, USAGE};
+use crate::limits::{BatchSummary, CancelToken, SolveResult};
+use crate::puzzle::{parse_moves, serialize_moves};
+use crate::puzzle_set::PuzzleSet;
+use crate::traits::puzzle::DebugPrintable;
+use crate::validate::validate;
+
+use crate::traits::puzzle::Heuristic;
+
+fn solve_all(options: &Options, cancel: &CancelToken) -> Result<(), Box<dyn std::error::Error>> {
+    let mut config = options.solve_config();
+    config.validate()?;
+    config.limits.cancel = Some(cancel.clone());
+    config.limits.deadline = options.batch_time_limit.map(|limit| Instant::now() + limit);
+
+    let puzzles = PuzzleSet::load(&options.input)?;
+    for (idx, e) in puzzles.errors() {
+        eprintln!("skipping puzzle {}: {}", idx, e);
+    }
+
+    let mut pool = rayon::ThreadPoolBuilder::new();
+    if let Some(threads) = options.threads {
+        pool = pool.num_threads(threads);
+    }
+    let pool = pool.build()?;
+
+    let progress = ProgressBar::new(puzzles.len() as u64);
+    progress.set_style(ProgressStyle::with_template("{bar:40} {pos}/{len} solved: {msg} [{elapsed_precise}]")?);
+
+    let cache: Box<dyn SolutionCache> = if options.no_cache {
+        Box::new(MemoryCache::new())
+    } else {
+        match RedisCache::connect(&options.redis) {
+            Ok(cache) => Box::new(cache),
+            Err(e) => {
+                eprintln!("{}, falling back to an in-memory cache", e);
+                Box::new(MemoryCache::new())
+            }
+        }
+    };
+
+    let solved = std::sync::atomic::AtomicUsize::new(0);
+    let results: Vec<Option<SolveResult>> = pool.install(|| {
+        puzzles.puzzles.par_iter().map(|puzzle| {
+            // Puzzles not started before an interrupt are left unanswered
+            if cancel.is_cancelled() {
+                progress.inc(1);
+                return None;
+            }
+
+            let result = puzzle.as_ref().ok().cloned().and_then(|mut puzzle| {
+                cache::solve_cached(cache.as_ref(), &mut puzzle, &config)
+                    .map_err(|e| eprintln!("could not solve {}: {}", puzzle.serialized(), e))
+                    .ok()
+            });
+
+            if result.as_ref().is_some_and(|result| result.outcome.answer().is_some()) {
+                let count = solved.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
+                progress.set_message(count.to_string());
+            }
+            progress.inc(1);
+            result
+        }).collect()
+    });
+    progress.finish();
+
+    let mut summary = BatchSummary::default();
+    results.iter().for_each(|result| summary.add(result.as_ref()));
+    let answers = results.into_iter()
+        .map(|result| result.and_then(|result| result.outcome.answer().map(serialize_moves)).unwrap_or_default())
+        .collect();
+
+    // Answers from earlier runs on the same puzzles survive where they are shorter or new
+    let mut answers = AnswersFile::from_answers(answers);
+    let kept = AnswersFile::load(&options.output).map_or(0, |previous| answers.merge(&previous, &puzzles));
+    answers.write(&options.output)?;
+
+    if kept > 0 {
+        println!("kept {} answers from the previous {}", kept, options.output);
+    }
+    if cancel.is_cancelled() {
+        println!("interrupted, saved the {} answers finished so far to {}", answers.answered(), options.output);
+    } else {
+        println!("saved {} answers to {}", answers.answered(), options.output);
+    }
+    println!("{}", summary);
+
+    Ok(())
+}
+
+fn status(options: &Options) -> Result<(), Box<dyn std::error::Error>> {
+    let puzzles = PuzzleSet::load(&options.input)?;
+    let answers = AnswersFile::load(&options.output).unwrap_or_default();
+
+    let answered = answers.answered();
+    let mut used = MoveCounts::default();
+    let mut malformed = 0;
+    for moves in &answers.answers {
+        match MoveCounts::from_moves(moves) {
+            Ok(counts) => used += counts,
+            Err(_) => malformed += 1,
+        }
+    }
+    let limits = options.limits.unwrap_or(puzzles.limits);
+
+    println!("puzzles:  {} ({})", puzzles.len(), options.input);
+    println!("answered: {} ({})", answered, options.output);
+    if malformed > 0 {
+        println!("malformed: {} answers are not made of moves and are not counted, run validate", malformed);
+    }
+    println!("moves:    U {}/{} D {}/{} L {}/{} R {}/{}", used.up, limits.up, used.down, limits.down, used.left, limits.left, used.right, limits.right);
+
+    Ok(())
+}
+
+/// Shortens every answer in the answers file in place. Answers that do not replay on their
+/// puzzle are reported and kept as they are.
+fn optimise_answers(options: &Options) -> Result<(), Box<dyn std::error::Error>> {
+    let puzzles = PuzzleSet::load(&options.input)?;
+    let answers = AnswersFile::load(&options.output).map_err(|e| format!("{}: {}", options.output, e))?;
+
+    let optimised: Vec<String> = answers.answers.par_iter().enumerate().map(|(idx, answer)| {
+        let Some(Ok(puzzle)) = puzzles.puzzles.get(idx) else { return answer.to_string() };
+        if answer.is_empty() {
+            return String::new();
+        }
+
+        let shorten = || -> Result<String, Box<dyn std::error::Error>> {
+            let moves = parse_moves(answer)?;
+            Ok(serialize_moves(&optimise::optimise(puzzle, &moves, options.window)?))
+        };
+        match shorten() {
+            Ok(moves) => moves,
+            Err(e) => {
+                eprintln!("keeping answer {} as it is: {}", idx, e);
+                answer.to_string()
+            }
+        }
+    }).collect();
+
+    let before: usize = answers.answers.iter().map(|answer| answer.len()).sum();
+    let after: usize = optimised.iter().map(|answer| answer.len()).sum();
+    AnswersFile::from_answers(optimised).write(&options.output)?;
+    println!("shortened the answers in {} from {} to {} moves", options.output, before, after);
+
+    Ok(())
+}
+
+/// Replays every answer on its puzzle, so a broken answers file is caught before a submission
+/// is spent on it
+fn validate_answers(options: &Options) -> Result<(), Box<dyn std::error::Error>> {
+    let puzzles = PuzzleSet::load(&options.input)?;
+    let answers = AnswersFile::load(&options.output).map_err(|e| format!("{}: {}", options.output, e))?;
+    let limits = options.limits.unwrap_or(puzzles.limits);
+
+    let (mut valid, mut invalid) = (0, 0);
+    let mut used = MoveCounts::default();
+    for (idx, answer) in answers.answers.iter().enumerate().filter(|(_, answer)| !answer.is_empty()) {
+        let report = match puzzles.puzzles.get(idx) {
+            Some(Ok(puzzle)) => validate(puzzle, answer).map_err(|e| e.to_string()),
+            Some(Err(e)) => Err(format!("puzzle is malformed: {}", e)),
+            None => Err(format!("no puzzle at this index ({} puzzles in {})", puzzles.len(), options.input)),
+        };
+
+        match report {
+            Ok(report) if report.is_valid() => {
+                valid += 1;
+                used += report.counts;
+            },
+            Ok(report) => {
+                invalid += 1;
+                println!("answer {}: {}", idx, report);
+            },
+            Err(e) => {
+                invalid += 1;
+                println!("answer {}: {}", idx, e);
+            },
+        }
+    }
+
+    println!("valid:    {} of {} answers ({})", valid, valid + invalid, options.output);
+    println!("moves:    U {}/{} D {}/{} L {}/{} R {}/{}", used.up, limits.up, used.down, limits.down, used.left, limits.left, used.right, limits.right);
+    if !used.fits_within(&limits) {
+        println!("the valid answers exceed the move limits, run plan to choose which to submit");
+    }
+
+    if invalid > 0 {
+        return Err(format!("{} answers failed validation", invalid).into());
+    }
+    Ok(())
+}
+
+fn show(options: &Options, index: usize) -> Result<(), Box<dyn std::error::Error>> {
+    let puzzles = PuzzleSet::load(&options.input)?;
+    let puzzle = match puzzles.puzzles.get(index) {
+        Some(Ok(puzzle)) => puzzle,
+        Some(Err(e)) => return Err(format!("puzzle {} is malformed: {}", index, e).into()),
+        None => return Err(format!("no puzzle at index {} ({} puzzles in {})", index, puzzles.len(), options.input).into()),
+    };
+
+    println!("{}", puzzle.serialized());
+    puzzle.debug_print(false);
+    println!("heuristic: {}", puzzle.get_heuristic(&puzzle.solved()));
+
+    if let Err(e) = puzzle.check_solvable() {
+        println!("{}", e);
+    }
+
+    Ok(())
+}
+
+fn plan(options: &Options) -> Result<BudgetPlan, Box<dyn std::error::Error>> {
+    let puzzles = PuzzleSet::load(&options.input)?;
+    let limits = options.limits.unwrap_or(puzzles.limits);
+    let mut planner = BudgetPlanner::new(limits, puzzles.len());
+
+    for path in std::iter::once(&options.output).chain(&options.candidates) {
+        let answers = AnswersFile::load(path).map_err(|e| format!("{}: {}", path, e))?;
+        for (idx, moves) in answers.answers.iter().enumerate().filter(|(_, moves)| !moves.is_empty()) {
+            // A corrupted answer is never planned, it could not be charged to the budget honestly
+            if let Err(e) = planner.add_candidate(idx, moves) {
+                eprintln!("skipping answer {} in {}: {}", idx, path, e);
+            }
+        }
+    }
+
+    Ok(planner.plan())
+}
+
+/// Cancels the batch on the first Ctrl-C, so the answers finished so far are still saved, and
+/// quits straight away on the second
+async fn cancel_on_interrupt(cancel: CancelToken) {
+    if tokio::signal::ctrl_c().await.is_ok() {
+        eprintln!("\ninterrupted, stopping the solves in flight (Ctrl-C again to quit now)");
+        cancel.cancel();
+
+        if tokio::signal::ctrl_c().await.is_ok() {
+            std::process::exit(130);
+        }
+    }
+}
+
+async fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
+    let options = &cli.options;
+
+    match cli.command {
+        Command::Help => println!("{}", USAGE),
+        Command::Fetch { count } => api::get_slide_puzzle(&Client::new(), &options.api_url, count, &options.input).await?,
+        Command::Solve => {
+            let cancel = CancelToken::new();
+            let interrupt = tokio::spawn(cancel_on_interrupt(cancel.clone()));
+            // Solving blocks, the signal handler keeps running on the other runtime threads
+            let result = tokio::task::block_in_place(|| solve_all(options, &cancel));
+            interrupt.abort();
+            result?
+        },
+        Command::Submit => {
+            let plan = plan(options)?;
+            print!("{}", plan);
+
+            let answers = format!("{}.planned", options.output);
+            AnswersFile::from_answers(plan.answers).write(&answers)?;
+
+            let response = api::submit_puzzle(&Client::new(), &options.api_url, &options.input, &answers).await?;
+            println!("{:#?}", response);
+        },
+        Command::Plan => print!("{}", plan(options)?),
+        Command::Optimise => optimise_answers(options)?,
+        Command::Validate => validate_answers(options)?,
+        Command::Status => status(options)?,
+        Command::Show { index } => show(options, index)?,
+    }
+
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = match Cli::parse(std::env::args().skip(1)) {
+        Ok(cli) => cli,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    match run(cli).await {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,267 @@
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use crate::answers::AnswersFile;
+use crate::api::PuzzleSubmissionResponse;
+use crate::budget::MoveCounts;
+use crate::puzzle::Puzzle;
+use crate::puzzle_set::PuzzleSet;
+use crate::validate::validate;
+
+/// Path the real API serves from, kept so the client builds the same URLs against the mock
+const API_PATH: &str = "/v1/recruitment/slidepuzzle";
+/// Tile labels in goal order, a 7x7 board uses all but the last
+const LABELS: &str = "123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+/// Longest random walk used to scramble a generated board
+const MAX_SCRAMBLE: usize = 16;
+
+/// Stand-in for the MetroWeather slide puzzle API on a local port, so `fetch` and `submit` run
+/// without network access. It serves the same two routes: `GET <url>/generate?count=<n>` returns
+/// a puzzle file, and `POST <url>` takes a multipart form with `questions` and `answers` files
+/// and scores it. The server stops when the `MockApi` is dropped.
+pub struct MockApi {
+    addr: SocketAddr,
+    submissions: Arc<Mutex<Vec<PuzzleSubmissionResponse>>>,
+    server: JoinHandle<()>,
+}
+
+impl MockApi {
+    /// Listens on a free port, generating puzzle sets from `seed`
+    pub async fn start(seed: u64) -> io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let submissions = Arc::new(Mutex::new(vec![]));
+
+        let scored = submissions.clone();
+        let server = tokio::spawn(async move {
+            let mut seed = seed;
+            while let Ok((stream, _)) = listener.accept().await {
+                seed = seed.wrapping_add(1);
+                let scored = scored.clone();
+                tokio::spawn(async move {
+                    let _ = serve(stream, seed, scored).await;
+                });
+            }
+        });
+
+        Ok(Self { addr, submissions, server })
+    }
+
+    /// Base URL to pass as `--api-url`
+    pub fn url(&self) -> String {
+        format!("http://{}{}", self.addr, API_PATH)
+    }
+
+    /// Every scored submission so far, oldest first
+    pub fn submissions(&self) -> Vec<PuzzleSubmissionResponse> {
+        self.submissions.lock().unwrap().clone()
+    }
+}
+
+impl Drop for MockApi {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+/// A puzzle file as the real API writes it: a `left right up down count` header, then one board
+/// per line. Boards are 3x3 to 7x7, some with a wall or two, and scrambled by a short random walk
+/// so they are never already solved. The limits allow seven moves each way per puzzle.
+pub fn generate(count: usize, seed: u64) -> String {
+    let mut state = seed | 1;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state as usize
+    };
+
+    let limit = count * 7;
+    let mut lines = vec![format!("{} {} {} {} {}", limit, limit, limit, limit, count)];
+    for _ in 0..count {
+        lines.push(scrambled_board(&mut next).serialized());
+    }
+
+    lines.join("\n")
+}
+
+/// A board of random size and walls moved away from solved by a random walk. Boards whose walls
+/// shut the blank in are drawn again.
+fn scrambled_board(next: &mut impl FnMut() -> usize) -> Puzzle {
+    loop {
+        let (width, height) = (3 + next() % 5, 3 + next() % 5);
+        let cells = width * height;
+        // Walls never take the blank's cell
+        let walls: Vec<usize> = (0..next() % 3).map(|_| next() % (cells - 1)).collect();
+        let tiles: String = LABELS.chars().take(cells - 1).enumerate()
+            .map(|(idx, label)| if walls.contains(&idx) { '=' } else { label })
+            .chain(std::iter::once('0'))
+            .collect();
+
+        let solved = Puzzle::from_str(&format!("{},{},{}", width, height, tiles)).expect("generated boards are well formed");
+        if solved.legal_moves(solved.space_idx()).is_empty() {
+            continue;
+        }
+
+        let mut puzzle = solved.clone();
+        while puzzle.is_solved(&solved) {
+            for _ in 0..1 + next() % MAX_SCRAMBLE {
+                let moves = puzzle.legal_moves(puzzle.space_idx());
+                puzzle.move_space(moves[next() % moves.len()]).expect("legal moves can be made");
+            }
+        }
+        return puzzle;
+    }
+}
+
+/// Scores a submission: the percentage of puzzles in `questions` whose answer solves them, or
+/// 0 when the moves across all answers exceed any of the limits in its header
+pub fn score(questions: &str, answers: &str) -> Result<PuzzleSubmissionResponse, String> {
+    let puzzles = PuzzleSet::parse(questions).map_err(|e| e.to_string())?;
+    let answers = AnswersFile::parse(answers);
+    if answers.len() > puzzles.len() {
+        return Err(format!("{} answers for {} puzzles", answers.len(), puzzles.len()));
+    }
+
+    let mut solved = 0;
+    for (idx, moves) in answers.answers.iter().enumerate().filter(|(_, moves)| !moves.is_empty()) {
+        let solves = puzzles.get(idx).is_some_and(|puzzle| validate(puzzle, moves).is_ok_and(|report| report.is_valid()));
+        solved += solves as usize;
+    }
+
+    let mut counts = MoveCounts::default();
+    for (idx, moves) in answers.answers.iter().enumerate() {
+        counts += MoveCounts::from_moves(moves).map_err(|e| format!("answer {}: {}", idx, e))?;
+    }
+    let limits = puzzles.limits;
+    let score = if counts.fits_within(&limits) && !puzzles.is_empty() { 100. * solved as f32 / puzzles.len() as f32 } else { 0. };
+
+    Ok(PuzzleSubmissionResponse {
+        response_time: chrono::Utc::now().to_rfc3339(),
+        score,
+        limit_up: limits.up,
+        limit_down: limits.down,
+        limit_left: limits.left,
+        limit_right: limits.right,
+        count_up: counts.up,
+        count_down: counts.down,
+        count_left: counts.left,
+        count_right: counts.right,
+    })
+}
+
+struct Request {
+    method: String,
+    target: String,
+    headers: HashMap<String, String>,
+    body: Vec<u8>,
+}
+
+/// Answers a single request and closes the connection
+async fn serve(stream: TcpStream, seed: u64, submissions: Arc<Mutex<Vec<PuzzleSubmissionResponse>>>) -> io::Result<()> {
+    let mut stream = BufReader::new(stream);
+    let request = read_request(&mut stream).await?;
+    let (path, query) = request.target.split_once('?').unwrap_or((&request.target, ""));
+
+    let (status, content_type, body) = match (request.method.as_str(), path.strip_prefix(API_PATH)) {
+        ("GET", Some("/generate")) => {
+            let count = query.split('&').find_map(|pair| pair.strip_prefix("count=")).and_then(|count| count.parse().ok());
+            match count {
+                Some(count) => ("200 OK", "text/plain", generate(count, seed)),
+                None => ("400 Bad Request", "text/plain", "count is missing or not a number".to_string()),
+            }
+        },
+        ("POST", Some("")) => {
+            let response = multipart_files(&request).and_then(|files| {
+                let file = |name: &str| files.get(name).ok_or_else(|| format!("{} file is missing", name));
+                score(file("questions")?, file("answers")?)
+            });
+            match response {
+                Ok(response) => {
+                    let json = serde_json::to_string(&response).expect("responses serialize");
+                    submissions.lock().unwrap().push(response);
+                    ("200 OK", "application/json", json)
+                },
+                Err(e) => ("400 Bad Request", "text/plain", e),
+            }
+        },
+        _ => ("404 Not Found", "text/plain", format!("no route for {} {}", request.method, path)),
+    };
+
+    let head = format!("HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", status, content_type, body.len());
+    let stream = stream.get_mut();
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+async fn read_request(stream: &mut BufReader<TcpStream>) -> io::Result<Request> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+    let mut line = String::new();
+    stream.read_line(&mut line).await?;
+    let mut parts = line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => (method.to_string(), target.to_string()),
+        _ => return Err(invalid("malformed request line")),
+    };
+
+    let mut headers = HashMap::new();
+    loop {
+        line.clear();
+        stream.read_line(&mut line).await?;
+        let Some((name, value)) = line.trim_end().split_once(':') else { break };
+        headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+    }
+
+    let mut body = vec![];
+    if headers.get("transfer-encoding").is_some_and(|encoding| encoding.eq_ignore_ascii_case("chunked")) {
+        loop {
+            line.clear();
+            stream.read_line(&mut line).await?;
+            let size = usize::from_str_radix(line.trim().split(';').next().unwrap_or(""), 16).map_err(|_| invalid("malformed chunk size"))?;
+            let start = body.len();
+            body.resize(start + size, 0);
+            stream.read_exact(&mut body[start..]).await?;
+            line.clear();
+            stream.read_line(&mut line).await?;
+            if size == 0 {
+                break;
+            }
+        }
+    } else if let Some(length) = headers.get("content-length") {
+        body.resize(length.parse().map_err(|_| invalid("malformed content length"))?, 0);
+        stream.read_exact(&mut body).await?;
+    }
+
+    Ok(Request { method, target, headers, body })
+}
+
+/// The parts of a `multipart/form-data` body by field name
+fn multipart_files(request: &Request) -> Result<HashMap<String, String>, String> {
+    let content_type = request.headers.get("content-type").ok_or("content type is missing")?;
+    let boundary = content_type.split(';')
+        .find_map(|param| param.trim().strip_prefix("boundary="))
+        .ok_or("not a multipart form")?;
+    let delimiter = format!("--{}", boundary.trim_matches('"'));
+    let body = String::from_utf8_lossy(&request.body);
+
+    let mut files = HashMap::new();
+    // Skips the preamble before the first delimiter and stops at the closing one
+    for part in body.split(delimiter.as_str()).skip(1).take_while(|part| !part.starts_with("--")) {
+        let part = part.strip_prefix("\r\n").unwrap_or(part);
+        let (head, contents) = part.split_once("\r\n\r\n").ok_or("malformed form part")?;
+        let name = head.lines()
+            .find(|line| line.to_lowercase().starts_with("content-disposition"))
+            .and_then(|line| line.split(';').find_map(|param| param.trim().strip_prefix("name=")))
+            .ok_or("form part without a name")?;
+        files.insert(name.trim_matches('"').to_string(), contents.strip_suffix("\r\n").unwrap_or(contents).to_string());
+    }
+
+    Ok(files)
+}
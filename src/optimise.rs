@@ -0,0 +1,236 @@
+use std::fmt;
+use rustc_hash::FxHashMap;
+use crate::puzzle::{Direction, Puzzle, PuzzleError};
+
+/// Longest stretch of an answer the window search tries to replace with a shorter route
+pub const DEFAULT_WINDOW: usize = 12;
+/// Boards one window search may visit before the stretch is left as it is
+const MAX_WINDOW_NODES: usize = 20_000;
+
+#[derive(Debug)]
+pub enum OptimiseError {
+    /// The move at this index cannot be made on the board the answer belongs to
+    IllegalMove(usize, PuzzleError),
+    /// The shortened answer ends on another layout than the original, which is a bug
+    Diverged,
+}
+
+impl fmt::Display for OptimiseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OptimiseError::IllegalMove(step, e) => write!(f, "Move {} of the answer: {}", step + 1, e),
+            OptimiseError::Diverged => write!(f, "Shortened answer does not end on the same board"),
+        }
+    }
+}
+
+impl std::error::Error for OptimiseError {}
+
+/// Shortens `moves` without changing the board they lead to from `puzzle`. Moves undone straight
+/// away are cancelled, loops back to a layout visited before are cut out, and every stretch of
+/// up to `window` moves is swapped for a shorter route between the same two layouts when an
+/// IDA* search of at most `MAX_WINDOW_NODES` boards finds one. The passes repeat until none of
+/// them helps, and the result is replayed on `puzzle` before it is returned.
+pub fn optimise(puzzle: &Puzzle, moves: &[Direction], window: usize) -> Result<Vec<Direction>, OptimiseError> {
+    let end = replay(puzzle, moves)?;
+    let mut moves = moves.to_vec();
+
+    loop {
+        let before = moves.len();
+        moves = cancel_inverses(&moves);
+        moves = remove_cycles(puzzle, &moves);
+        moves = replace_windows(puzzle, &moves, window);
+        if moves.len() == before {
+            break;
+        }
+    }
+
+    match replay(puzzle, &moves) {
+        Ok(layout) if layout == end => Ok(moves),
+        _ => Err(OptimiseError::Diverged),
+    }
+}
+
+/// Drops every move that is undone by the next one, along with that next move
+pub fn cancel_inverses(moves: &[Direction]) -> Vec<Direction> {
+    let mut kept: Vec<Direction> = Vec::with_capacity(moves.len());
+    for &dir in moves {
+        if kept.last() == Some(&dir.inverse()) {
+            kept.pop();
+        } else {
+            kept.push(dir);
+        }
+    }
+    kept
+}
+
+/// The layout `moves` lead to from `puzzle`, checking every move on the way
+fn replay(puzzle: &Puzzle, moves: &[Direction]) -> Result<Layout, OptimiseError> {
+    let mut board = puzzle.clone();
+    for (step, &dir) in moves.iter().enumerate() {
+        board.move_space(dir).map_err(|e| OptimiseError::IllegalMove(step, e))?;
+    }
+    Ok(Layout::new(&board))
+}
+
+/// Cuts every loop out of `moves`, so the answer never visits the same layout twice: from each
+/// layout it carries on from the last time the original answer was there
+fn remove_cycles(puzzle: &Puzzle, moves: &[Direction]) -> Vec<Direction> {
+    let mut board = Layout::new(puzzle);
+    let mut layouts = vec![board.cells.clone()];
+    for &dir in moves {
+        board.step(dir).expect("answers are checked before they are shortened");
+        layouts.push(board.cells.clone());
+    }
+
+    let last: FxHashMap<&[u8], usize> = layouts.iter().enumerate().map(|(idx, cells)| (cells.as_slice(), idx)).collect();
+    let mut kept = vec![];
+    let mut at = last[layouts[0].as_slice()];
+    while at < moves.len() {
+        kept.push(moves[at]);
+        at = last[layouts[at + 1].as_slice()];
+    }
+    kept
+}
+
+/// Walks `moves` from the start, replacing the next `window` moves whenever a shorter route
+/// between the same two layouts exists and otherwise keeping a single move
+fn replace_windows(puzzle: &Puzzle, moves: &[Direction], window: usize) -> Vec<Direction> {
+    let mut board = Layout::new(puzzle);
+    let mut kept = Vec::with_capacity(moves.len());
+    let mut at = 0;
+
+    while at < moves.len() {
+        let end = (at + window).min(moves.len());
+        let mut target = board.clone();
+        for &dir in &moves[at..end] {
+            target.step(dir).expect("answers are checked before they are shortened");
+        }
+
+        let route = match shortcut(&board, &target, end - at) {
+            Some(route) => {
+                at = end;
+                route
+            }
+            None => {
+                at += 1;
+                vec![moves[at - 1]]
+            }
+        };
+        for dir in route {
+            board.step(dir).expect("routes only make legal moves");
+            kept.push(dir);
+        }
+    }
+
+    kept
+}
+
+/// A route from `from` to `to` shorter than `length` moves, if IDA* finds one within
+/// `MAX_WINDOW_NODES` boards. Every route between two layouts has the same parity, so the bound
+/// grows two moves at a time.
+fn shortcut(from: &Layout, to: &Layout, length: usize) -> Option<Vec<Direction>> {
+    let mut search = WindowSearch::new(from, to);
+    let h = search.estimate();
+    let mut bound = h + (h + length) % 2;
+
+    while bound < length {
+        match search.descend(0, h, bound, None) {
+            Some(true) => return Some(search.route),
+            Some(false) => bound += 2,
+            None => return None,
+        }
+    }
+    None
+}
+
+/// Depth-first search towards a fixed layout, estimating with the manhattan distance of every
+/// tile to where the target has it
+struct WindowSearch {
+    board: Layout,
+    goals: [usize; 128],
+    route: Vec<Direction>,
+    nodes: usize,
+}
+
+impl WindowSearch {
+    fn new(from: &Layout, to: &Layout) -> Self {
+        let mut goals = [0; 128];
+        for (idx, &cell) in to.cells.iter().enumerate() {
+            goals[cell as usize] = idx;
+        }
+        Self { board: from.clone(), goals, route: vec![], nodes: 0 }
+    }
+
+    fn distance(&self, cell: u8, idx: usize) -> usize {
+        if cell == b'=' || cell == b'0' {
+            return 0;
+        }
+        let (goal, width) = (self.goals[cell as usize], self.board.width);
+        (idx / width).abs_diff(goal / width) + (idx % width).abs_diff(goal % width)
+    }
+
+    fn estimate(&self) -> usize {
+        self.board.cells.iter().enumerate().map(|(idx, &cell)| self.distance(cell, idx)).sum()
+    }
+
+    /// Whether the target is within `bound` moves, `None` once the node budget runs out
+    fn descend(&mut self, g: usize, h: usize, bound: usize, previous: Option<Direction>) -> Option<bool> {
+        if h == 0 {
+            return Some(true);
+        }
+        self.nodes += 1;
+        if self.nodes > MAX_WINDOW_NODES {
+            return None;
+        }
+
+        for dir in Direction::ALL {
+            if previous == Some(dir.inverse()) {
+                continue;
+            }
+            let Some(from) = self.board.step(dir) else { continue };
+            // The tile the blank swapped with now sits where the blank came from
+            let cell = self.board.cells[from];
+            let h = h + self.distance(cell, from) - self.distance(cell, self.board.blank);
+
+            self.route.push(dir);
+            if g + 1 + h <= bound {
+                match self.descend(g + 1, h, bound, Some(dir)) {
+                    Some(false) => {}
+                    found => return found,
+                }
+            }
+            self.route.pop();
+            self.board.step(dir.inverse());
+        }
+
+        Some(false)
+    }
+}
+
+/// Just the cells and the blank, cheap to copy and compare while replaying long answers
+#[derive(Clone, Debug, PartialEq)]
+struct Layout {
+    cells: Vec<u8>,
+    width: usize,
+    blank: usize,
+}
+
+impl Layout {
+    fn new(puzzle: &Puzzle) -> Self {
+        let cells = puzzle.tiles.iter().map(|tile| tile.raw as u8).collect();
+        Self { cells, width: puzzle.width as usize, blank: puzzle.space_idx() }
+    }
+
+    /// Moves the blank, returning the cell it came from, unless it would leave the board or
+    /// enter a wall
+    fn step(&mut self, dir: Direction) -> Option<usize> {
+        let next = dir.step(self.blank, self.width, self.cells.len())?;
+        if self.cells[next] == b'=' {
+            return None;
+        }
+
+        self.cells.swap(self.blank, next);
+        Some(std::mem::replace(&mut self.blank, next))
+    }
+}
@@ -0,0 +1,469 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Mutex, OnceLock};
+use rustc_hash::FxHashMap;
+use crate::heuristic::{BoardHeuristic, Memo};
+use crate::puzzle::Puzzle;
+use crate::state::PackedState;
+use crate::tile::Rune::{VALUE, WALL};
+
+pub const DEFAULT_PDB_DIR: &str = "pdb";
+
+const MAGIC: &[u8; 4] = b"SPDB";
+const VERSION: u8 = 1;
+/// Largest pattern a database is built for, 6-6-3 on 4x4 and 5-5-5-5-4 on 5x5
+const MAX_GROUP_SIZE: usize = 6;
+/// Cap on `(pattern placements) x (blank cells)` searched per group of a shared database, which
+/// bounds the memory used while building. It fits groups of 5 on an open 5x5 board, which take
+/// about two minutes and 250 MB to build. Larger boards fall back to smaller groups.
+const MAX_SEARCH_STATES: usize = 1 << 28;
+/// The same cap for a layout asked for only once, whose database serves one board and is dropped
+/// again. Every thread may be building one, so they are kept small.
+const MAX_ONE_OFF_STATES: usize = 1 << 22;
+const UNVISITED: u8 = u8::MAX;
+
+/// Board shape and wall cells, the only things a database depends on. Tiles are matched to
+/// patterns by their goal cell rather than their label, so every puzzle with the same layout
+/// shares one database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Layout {
+    pub width: u8,
+    pub height: u8,
+    pub walls: u64,
+}
+
+impl Layout {
+    pub fn of(puzzle: &Puzzle) -> Self {
+        let walls = puzzle.tiles.iter()
+            .enumerate()
+            .filter(|(_, tile)| tile.rune == WALL)
+            .fold(0, |walls, (idx, _)| walls | 1 << idx);
+
+        Self { width: puzzle.width as u8, height: puzzle.height as u8, walls }
+    }
+
+    pub fn file_name(&self) -> String {
+        format!("{}x{}-{:013x}.pdb", self.width, self.height, self.walls)
+    }
+}
+
+#[derive(Debug)]
+pub enum PatternDbError {
+    Io(io::Error),
+    Corrupt(String),
+    LayoutMismatch { expected: Layout, found: Layout },
+}
+
+impl fmt::Display for PatternDbError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PatternDbError::Io(e) => write!(f, "Could not access pattern database: {}", e),
+            PatternDbError::Corrupt(msg) => write!(f, "Corrupt pattern database: {}", msg),
+            PatternDbError::LayoutMismatch { expected, found } => write!(f, "Pattern database is for {}, expected {}", found.file_name(), expected.file_name()),
+        }
+    }
+}
+
+impl std::error::Error for PatternDbError {}
+
+impl From<io::Error> for PatternDbError {
+    fn from(e: io::Error) -> Self {
+        PatternDbError::Io(e)
+    }
+}
+
+/// Disjoint additive pattern databases for one layout. The tiles are split into groups, and
+/// for every placement of a group's tiles the table holds the fewest moves of those tiles
+/// needed to bring them home, found by a backward breadth-first search from the solved board
+/// in which moving any other tile is free. Since no move is counted by two groups, the sum
+/// over all groups never overestimates.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatternDatabase {
+    layout: Layout,
+    /// Board cell of every open (non wall) cell
+    open: Vec<usize>,
+    groups: Vec<Pattern>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Pattern {
+    /// Goal cells of the tiles in this group, as indices into `open`
+    goals: Vec<u8>,
+    /// Moves left, indexed by `placement_index` of the tiles' current open cells
+    table: Vec<u8>,
+}
+
+impl PatternDatabase {
+    /// Builds with groups of the largest size the layout allows, taking goal cells in reading
+    /// order.
+    pub fn build(solved_puzzle: &Puzzle) -> Self {
+        Self::build_within(solved_puzzle, MAX_SEARCH_STATES)
+    }
+
+    /// Builds with the largest groups whose search stays within `max_states`
+    fn build_within(solved_puzzle: &Puzzle, max_states: usize) -> Self {
+        Self::build_with_groups(solved_puzzle, groups_within(solved_puzzle, max_states))
+    }
+
+    /// Tile counts of the groups `build` would use for this layout
+    #[cfg(test)]
+    pub fn planned_group_sizes(solved_puzzle: &Puzzle) -> Vec<usize> {
+        groups_within(solved_puzzle, MAX_SEARCH_STATES).iter().map(|group| group.len()).collect()
+    }
+
+    /// Builds one table per group, each group listing the goal cells of its tiles
+    pub fn build_with_groups(solved_puzzle: &Puzzle, groups: Vec<Vec<usize>>) -> Self {
+        let layout = Layout::of(solved_puzzle);
+        let open: Vec<usize> = (0..solved_puzzle.tiles.len()).filter(|idx| layout.walls & 1 << idx == 0).collect();
+        let open_idx = |cell: usize| open.iter().position(|&open_cell| open_cell == cell).unwrap() as u8;
+
+        let width = layout.width as usize;
+        let neighbours: Vec<Vec<u8>> = open.iter().map(|&cell| {
+            let (row, col) = (cell / width, cell % width);
+            let mut adjacent = vec![];
+            if row > 0 { adjacent.push(cell - width); }
+            if row + 1 < layout.height as usize { adjacent.push(cell + width); }
+            if col > 0 { adjacent.push(cell - 1); }
+            if col + 1 < width { adjacent.push(cell + 1); }
+            adjacent.into_iter().filter(|cell| layout.walls & 1 << cell == 0).map(open_idx).collect()
+        }).collect();
+
+        let blank = open_idx(solved_puzzle.space_idx());
+        let groups = groups.into_iter().map(|goals| {
+            let goals: Vec<u8> = goals.into_iter().map(open_idx).collect();
+            let table = search(&goals, blank, &neighbours);
+            Pattern { goals, table }
+        }).collect();
+
+        Self { layout, open, groups }
+    }
+
+    #[cfg(test)]
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    #[cfg(test)]
+    pub fn group_sizes(&self) -> Vec<usize> {
+        self.groups.iter().map(|group| group.goals.len()).collect()
+    }
+
+    /// Writes `SPDB`, a version byte, the layout, then every group as its goal cells followed by
+    /// its table. All integers are little endian.
+    pub fn save(&self, path: &Path) -> Result<(), PatternDbError> {
+        let mut bytes = Vec::with_capacity(16 + self.groups.iter().map(|group| group.table.len() + 16).sum::<usize>());
+        bytes.extend_from_slice(MAGIC);
+        bytes.push(VERSION);
+        bytes.push(self.layout.width);
+        bytes.push(self.layout.height);
+        bytes.extend_from_slice(&self.layout.walls.to_le_bytes());
+        bytes.push(self.groups.len() as u8);
+
+        for group in &self.groups {
+            bytes.push(group.goals.len() as u8);
+            bytes.extend_from_slice(&group.goals);
+            bytes.extend_from_slice(&(group.table.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(&group.table);
+        }
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // Write then rename so a crash never leaves a truncated database behind
+        let tmp = path.with_extension("pdb.tmp");
+        fs::write(&tmp, bytes)?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, PatternDbError> {
+        let bytes = fs::read(path)?;
+        let mut reader = Reader { bytes: &bytes, offset: 0 };
+
+        if reader.take(4)? != MAGIC {
+            return Err(PatternDbError::Corrupt("missing SPDB header".into()));
+        }
+        let version = reader.u8()?;
+        if version != VERSION {
+            return Err(PatternDbError::Corrupt(format!("unsupported version {}", version)));
+        }
+
+        let width = reader.u8()?;
+        let height = reader.u8()?;
+        let walls = reader.u64()?;
+        let layout = Layout { width, height, walls };
+        let open: Vec<usize> = (0..width as usize * height as usize).filter(|idx| walls & 1 << idx == 0).collect();
+
+        let group_count = reader.u8()?;
+        let mut groups = Vec::with_capacity(group_count as usize);
+        for _ in 0..group_count {
+            let size = reader.u8()? as usize;
+            let goals = reader.take(size)?.to_vec();
+            let len = reader.u64()? as usize;
+            if len != placements(open.len(), size) || goals.iter().any(|&goal| goal as usize >= open.len()) {
+                return Err(PatternDbError::Corrupt(format!("group of {} tiles does not fit {}", size, layout.file_name())));
+            }
+            let table = reader.take(len)?.to_vec();
+            groups.push(Pattern { goals, table });
+        }
+
+        if reader.offset != bytes.len() {
+            return Err(PatternDbError::Corrupt("trailing data".into()));
+        }
+
+        Ok(Self { layout, open, groups })
+    }
+
+    /// Loads the database for this layout from `dir`, building and saving it if it is missing or
+    /// unreadable.
+    pub fn load_or_build(solved_puzzle: &Puzzle, dir: &Path) -> Result<Self, PatternDbError> {
+        let layout = Layout::of(solved_puzzle);
+        let path = dir.join(layout.file_name());
+
+        match Self::load(&path) {
+            Ok(db) if db.layout == layout => return Ok(db),
+            Ok(db) => eprintln!("{}, rebuilding", PatternDbError::LayoutMismatch { expected: layout, found: db.layout }),
+            Err(PatternDbError::Io(e)) if e.kind() == io::ErrorKind::NotFound => {},
+            Err(e) => eprintln!("{}, rebuilding", e),
+        }
+
+        let db = Self::build(solved_puzzle);
+        db.save(&path)?;
+        Ok(db)
+    }
+
+    /// Prepares the lookups for one tile set on this layout
+    pub fn heuristic(self: &Arc<Self>, solved_puzzle: &Puzzle) -> PatternHeuristic {
+        let mut slots = [None; 64];
+        for (group_idx, group) in self.groups.iter().enumerate() {
+            for (slot, &goal) in group.goals.iter().enumerate() {
+                let tile = solved_puzzle.tiles[self.open[goal as usize]];
+                slots[tile.rank() as usize] = Some((group_idx as u8, slot as u8));
+            }
+        }
+
+        let mut open_idx = vec![u8::MAX; solved_puzzle.tiles.len()];
+        for (idx, &cell) in self.open.iter().enumerate() {
+            open_idx[cell] = idx as u8;
+        }
+
+        PatternHeuristic { db: Arc::clone(self), slots, open_idx }
+    }
+}
+
+pub struct PatternHeuristic {
+    db: Arc<PatternDatabase>,
+    /// Group and slot of every tile rank
+    slots: [Option<(u8, u8)>; 64],
+    open_idx: Vec<u8>,
+}
+
+impl BoardHeuristic for PatternHeuristic {
+    fn evaluate(&self, state: &PackedState, _: &mut Memo) -> u32 {
+        let mut placements = [[0u8; MAX_GROUP_SIZE]; 64];
+
+        for (idx, &open_idx) in self.open_idx.iter().enumerate() {
+            if let Some((group, slot)) = self.slots[state.get(idx) as usize] {
+                placements[group as usize][slot as usize] = open_idx;
+            }
+        }
+
+        self.db.groups.iter()
+            .zip(placements)
+            .map(|(group, placement)| {
+                group.table[placement_index(&placement[..group.goals.len()], self.db.open.len())] as u32
+            })
+            .sum()
+    }
+}
+
+/// The databases built for one layout, each filled in once by the first thread to need it
+#[derive(Default)]
+struct LayoutDatabases {
+    one_off: OnceLock<Arc<PatternDatabase>>,
+    full: OnceLock<Arc<PatternDatabase>>,
+}
+
+/// Every layout asked for so far
+static DATABASES: Mutex<Option<FxHashMap<Layout, Arc<LayoutDatabases>>>> = Mutex::new(None);
+/// Held while a full database is built, so threads never build two at once
+static BUILDING: Mutex<()> = Mutex::new(());
+
+/// The database for the solved board's layout. Puzzle sets rarely repeat a wall layout, so the
+/// first board with a layout gets a small database built within `MAX_ONE_OFF_STATES`. A layout
+/// asked for again, or saved in `dir` by an earlier run, gets a full database that is saved in
+/// `dir` if there is one. Both are kept for the rest of the process, and while another thread
+/// builds a full database the small one is handed out instead of waiting for it.
+pub fn shared(solved_puzzle: &Puzzle, dir: Option<&Path>) -> Arc<PatternDatabase> {
+    let layout = Layout::of(solved_puzzle);
+    let (databases, reused) = {
+        let mut databases = DATABASES.lock().unwrap();
+        let databases = databases.get_or_insert_with(FxHashMap::default);
+        let reused = databases.contains_key(&layout);
+        (databases.entry(layout).or_default().clone(), reused)
+    };
+
+    if let Some(db) = databases.full.get() {
+        return db.clone();
+    }
+    if let Some(db) = dir.and_then(|dir| PatternDatabase::load(&dir.join(layout.file_name())).ok()).filter(|db| db.layout == layout) {
+        return databases.full.get_or_init(|| Arc::new(db)).clone();
+    }
+    if !reused {
+        return databases.one_off.get_or_init(|| Arc::new(PatternDatabase::build_within(solved_puzzle, MAX_ONE_OFF_STATES))).clone();
+    }
+
+    let _building = match (BUILDING.try_lock().ok(), databases.one_off.get()) {
+        (Some(building), _) => building,
+        (None, Some(db)) => return db.clone(),
+        (None, None) => BUILDING.lock().unwrap(),
+    };
+    databases.full.get_or_init(|| {
+        let db = match dir {
+            Some(dir) => PatternDatabase::load_or_build(solved_puzzle, dir).unwrap_or_else(|e| {
+                eprintln!("{}, keeping the database in memory only", e);
+                PatternDatabase::build(solved_puzzle)
+            }),
+            None => PatternDatabase::build(solved_puzzle),
+        };
+        Arc::new(db)
+    }).clone()
+}
+
+/// Goal cells in reading order, split into the largest groups whose search stays within
+/// `max_states`
+fn groups_within(solved_puzzle: &Puzzle, max_states: usize) -> Vec<Vec<usize>> {
+    let open_cells = solved_puzzle.tiles.iter().filter(|tile| tile.rune != WALL).count();
+    let group_size = (1..=MAX_GROUP_SIZE)
+        .take_while(|&size| placements(open_cells, size).saturating_mul(open_cells) <= max_states)
+        .last()
+        .unwrap_or(1);
+
+    let goals: Vec<usize> = solved_puzzle.tiles.iter()
+        .enumerate()
+        .filter(|(_, tile)| tile.rune == VALUE)
+        .map(|(idx, _)| idx)
+        .collect();
+
+    goals.chunks(group_size).map(|chunk| chunk.to_vec()).collect()
+}
+
+/// Number of ways to place `size` distinct tiles on `cells` cells
+fn placements(cells: usize, size: usize) -> usize {
+    (cells + 1 - size.min(cells)..=cells).product()
+}
+
+/// Ranks distinct open cells in `0..placements(cells, positions.len())`. Each position is
+/// numbered among the cells not taken by the positions before it.
+fn placement_index(positions: &[u8], cells: usize) -> usize {
+    let mut index = 0;
+    let mut used = 0u64;
+
+    for (i, &position) in positions.iter().enumerate() {
+        let below = (used & ((1 << position) - 1)).count_ones() as usize;
+        index = index * (cells - i) + position as usize - below;
+        used |= 1 << position;
+    }
+
+    index
+}
+
+fn placement_from_index(mut index: usize, cells: usize, positions: &mut [u8]) {
+    let mut digits = [0; MAX_GROUP_SIZE];
+    for i in (0..positions.len()).rev() {
+        digits[i] = index % (cells - i);
+        index /= cells - i;
+    }
+
+    let mut used = 0u64;
+    for (position, &digit) in positions.iter_mut().zip(&digits) {
+        let mut remaining = digit;
+        let mut cell = 0;
+        loop {
+            if used & 1 << cell == 0 {
+                if remaining == 0 {
+                    break;
+                }
+                remaining -= 1;
+            }
+            cell += 1;
+        }
+        *position = cell;
+        used |= 1 << cell;
+    }
+}
+
+/// 0-1 breadth-first search over (placement, blank cell) from the goal. Moving a pattern tile
+/// costs 1 and moving any other tile is free, so the blank wanders for nothing and each
+/// placement ends up with the fewest pattern moves over every blank cell.
+fn search(goals: &[u8], blank: u8, neighbours: &[Vec<u8>]) -> Vec<u8> {
+    let cells = neighbours.len();
+    let size = placements(cells, goals.len());
+    let mut distances = vec![UNVISITED; size * cells];
+    let mut queue = VecDeque::new();
+
+    let start = placement_index(goals, cells) * cells + blank as usize;
+    distances[start] = 0;
+    queue.push_back(start);
+
+    let mut positions = [0u8; MAX_GROUP_SIZE];
+    let positions = &mut positions[..goals.len()];
+
+    while let Some(state) = queue.pop_front() {
+        let (placement, blank) = (state / cells, state % cells);
+        let distance = distances[state];
+        placement_from_index(placement, cells, positions);
+
+        for &next in &neighbours[blank] {
+            let (next_state, next_distance) = match positions.iter().position(|&position| position == next) {
+                Some(slot) => {
+                    positions[slot] = blank as u8;
+                    let next_placement = placement_index(positions, cells);
+                    positions[slot] = next;
+                    (next_placement * cells + next as usize, distance.saturating_add(1).min(UNVISITED - 1))
+                },
+                None => (placement * cells + next as usize, distance),
+            };
+
+            if next_distance < distances[next_state] {
+                distances[next_state] = next_distance;
+                if next_distance == distance {
+                    queue.push_front(next_state);
+                } else {
+                    queue.push_back(next_state);
+                }
+            }
+        }
+    }
+
+    distances.chunks(cells)
+        .map(|blanks| blanks.iter().copied().min().unwrap_or(UNVISITED))
+        .map(|distance| if distance == UNVISITED { 0 } else { distance })
+        .collect()
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], PatternDbError> {
+        let end = self.offset.checked_add(len).filter(|&end| end <= self.bytes.len())
+            .ok_or_else(|| PatternDbError::Corrupt("file is truncated".into()))?;
+        let bytes = &self.bytes[self.offset..end];
+        self.offset = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, PatternDbError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u64(&mut self) -> Result<u64, PatternDbError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+}
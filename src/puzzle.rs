@@ -1,15 +1,18 @@
 use std::cmp::Reverse;
 use std::collections::BinaryHeap;
 use std::fmt;
-use std::hash::{Hash, Hasher};
-use std::mem::MaybeUninit;
+use std::str::FromStr;
+use std::hash::Hash;
+use std::path::PathBuf;
 use std::thread::sleep;
-use rustc_hash::{FxHashSet, FxHasher};
-use crate::tile::Rune::{SPACE, WALL};
+use rustc_hash::{FxHashMap, FxHashSet};
+use crate::tile::Rune::{SPACE, VALUE, WALL};
+use crate::reduction;
+use crate::heuristic::{BoardHeuristic, HeuristicKind, Memo};
+use crate::limits::{Budget, Limit, SolveLimits, SolveOutcome, SolveResult, Stop};
+use crate::state::{tile_code, zobrist_hash, zobrist_move, PackedState, SearchTree, BLANK_CODE, WALL_CODE};
 use crate::tile::Tile;
-use crate::traits::puzzle::{DebugPrintable, Heuristic};
-use ordered_float::OrderedFloat;
-use rayon::prelude::*;
+use crate::traits::puzzle::DebugPrintable;
 
 #[derive(Debug, Clone, Copy, PartialEq, Hash, Eq, Ord, PartialOrd)]
 #[repr(u8)]
@@ -21,6 +24,9 @@ pub enum Direction {
 }
 
 impl Direction {
+    pub const ALL: [Direction; 4] = [Direction::UP, Direction::DOWN, Direction::LEFT, Direction::RIGHT];
+
+    #[allow(clippy::wrong_self_convention)]
     pub fn to_char(&self) -> char {
         match self {
             Direction::UP => 'U',
@@ -30,6 +36,16 @@ impl Direction {
         }
     }
 
+    pub fn from_char(char: char) -> Option<Direction> {
+        match char {
+            'U' => Some(Direction::UP),
+            'D' => Some(Direction::DOWN),
+            'L' => Some(Direction::LEFT),
+            'R' => Some(Direction::RIGHT),
+            _ => None
+        }
+    }
+
     pub fn inverse(&self) -> Direction {
         match self {
             Direction::UP => Direction::DOWN,
@@ -38,52 +54,264 @@ impl Direction {
             Direction::RIGHT => Direction::LEFT
         }
     }
+
+    /// The cell next to `idx` in this direction on a board `width` cells wide with `cells`
+    /// cells in all, unless it is off the board. Walls are left to the caller.
+    pub fn step(&self, idx: usize, width: usize, cells: usize) -> Option<usize> {
+        match self {
+            Direction::UP => idx.checked_sub(width),
+            Direction::DOWN => Some(idx + width).filter(|&next| next < cells),
+            Direction::LEFT => Some(idx).filter(|idx| idx % width != 0).map(|idx| idx - 1),
+            Direction::RIGHT => Some(idx + 1).filter(|next| next % width != 0),
+        }
+    }
 }
 
-pub fn hash_tiles(tiles: &Vec<Tile>) -> u64 {
-    let mut hasher = FxHasher::default();
+/// Cells of a row, left to right. Lines are at most 7 cells long.
+pub(crate) fn row_cells(row: usize, width: usize) -> ArrayLine {
+    ArrayLine::new((0..width).map(|col| row * width + col))
+}
 
-    for tile in tiles {
-        tile.hash(&mut hasher);
+/// Cells of a column, top to bottom
+pub(crate) fn column_cells(col: usize, width: usize, height: usize) -> ArrayLine {
+    ArrayLine::new((0..height).map(|row| row * width + col))
+}
+
+/// A row or column kept on the stack
+pub(crate) struct ArrayLine {
+    cells: [usize; 7],
+    len: usize,
+}
+
+impl ArrayLine {
+    fn new(cells: impl Iterator<Item = usize>) -> Self {
+        let mut line = Self { cells: [0; 7], len: 0 };
+        for cell in cells {
+            line.cells[line.len] = cell;
+            line.len += 1;
+        }
+        line
+    }
+}
+
+impl std::ops::Deref for ArrayLine {
+    type Target = [usize];
+
+    fn deref(&self) -> &[usize] {
+        &self.cells[..self.len]
     }
+}
 
-    hasher.finish()
+#[allow(clippy::ptr_arg)]
+pub fn hash_tiles(tiles: &Vec<Tile>) -> u64 {
+    zobrist_hash(tiles)
 }
 
 #[derive(Debug)]
 pub enum PuzzleError {
     IllegalMove(String),
-    UnsolvableBoard(String)
+    /// A character other than U, D, L or R at this index of a move string
+    UnknownMove(usize, char),
+    UnsolvableBoard(String),
+    InadmissibleHeuristic(String),
+    InvalidWeight(f32),
 }
 
 impl fmt::Display for PuzzleError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             PuzzleError::IllegalMove(msg) => write!(f, "Illegal move: {}", msg),
-            PuzzleError::UnsolvableBoard(msg) => write!(f, "Board is in an unsolvable configuration {}", msg)
+            PuzzleError::UnknownMove(idx, char) => write!(f, "Unknown move {:?} at {}", char, idx + 1),
+            PuzzleError::UnsolvableBoard(msg) => write!(f, "Board is in an unsolvable configuration: {}", msg),
+            PuzzleError::InadmissibleHeuristic(msg) => write!(f, "Heuristic may overestimate and cannot be used for an optimal search: {}", msg),
+            PuzzleError::InvalidWeight(weight) => write!(f, "Heuristic weight must be at least 1, got {}", weight),
         }
     }
 }
 
 impl std::error::Error for PuzzleError {}
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    AStar,
+    IdaStar,
+    Bidirectional,
+    /// A* ordered by g + w·h, answers are at most w times the optimal length
+    WeightedAStar,
+    /// Anytime repairing A*, weighted A* passes with a falling weight, see `Puzzle::solve_anytime`
+    AraStar,
+    /// Solves the board a line at a time, fast on any size but far from optimal, see `reduction`
+    Reduction,
+}
 
-pub enum PuzzleRouteDirection {
-    Forward,
-    Reverse
+impl FromStr for Algorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "astar" | "a*" => Ok(Algorithm::AStar),
+            "ida" | "idastar" | "ida*" => Ok(Algorithm::IdaStar),
+            "bidirectional" | "bidi" => Ok(Algorithm::Bidirectional),
+            "wastar" | "weighted" | "wa*" => Ok(Algorithm::WeightedAStar),
+            "ara" | "arastar" | "ara*" => Ok(Algorithm::AraStar),
+            "reduction" | "peel" => Ok(Algorithm::Reduction),
+            _ => Err(format!("unknown algorithm {}", s)),
+        }
+    }
 }
 
-pub struct PuzzleRoute {
-    state_hash: u64,
-    moves: Vec<Direction>,
-    direction: PuzzleRouteDirection
+impl fmt::Display for Algorithm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Algorithm::AStar => write!(f, "astar"),
+            Algorithm::IdaStar => write!(f, "ida"),
+            Algorithm::Bidirectional => write!(f, "bidirectional"),
+            Algorithm::WeightedAStar => write!(f, "wastar"),
+            Algorithm::AraStar => write!(f, "ara"),
+            Algorithm::Reduction => write!(f, "reduction"),
+        }
+    }
+}
+
+impl Algorithm {
+    /// IDA* promises optimal answers so it defaults to an admissible heuristic, A* keeps the
+    /// original heuristic it was tuned with.
+    pub fn default_heuristic(&self) -> HeuristicKind {
+        match self {
+            Algorithm::AStar => HeuristicKind::Legacy,
+            Algorithm::IdaStar | Algorithm::Bidirectional | Algorithm::Reduction => HeuristicKind::Manhattan,
+            Algorithm::WeightedAStar | Algorithm::AraStar => HeuristicKind::LinearConflict,
+        }
+    }
+
+    /// Starting weight on h, 1 for the algorithms that do not weight it
+    pub fn default_weight(&self) -> f32 {
+        match self {
+            Algorithm::WeightedAStar => 2.,
+            Algorithm::AraStar => 3.,
+            _ => 1.,
+        }
+    }
+
+    /// Algorithms that promise optimal answers, or answers within a bound of optimal, which
+    /// only holds while the heuristic never overestimates
+    pub fn needs_admissible_heuristic(&self) -> bool {
+        matches!(self, Algorithm::IdaStar | Algorithm::WeightedAStar | Algorithm::AraStar)
+    }
+
+    /// Weighted and anytime searches, which trade answer length for speed
+    pub fn is_weighted(&self) -> bool {
+        matches!(self, Algorithm::WeightedAStar | Algorithm::AraStar)
+    }
+}
+
+/// An answer found by `Puzzle::solve_anytime`, no more than `bound` times the optimal length
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnytimeSolution {
+    pub moves: Vec<Direction>,
+    pub bound: f32,
+    /// Weight of the pass that found it
+    pub weight: f32,
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct SolveConfig {
+    pub algorithm: Algorithm,
+    pub heuristic: HeuristicKind,
+    pub heuristic_threshold: f32,
+    /// Weight on h for weighted A*, and the starting weight for ARA*
+    pub weight: f32,
+    /// ARA* returns its best answer so far when these run out, every other search gives up
+    pub limits: SolveLimits,
+    /// Where pattern databases for layouts in use more than once are saved, none keeps them in
+    /// memory only
+    pub pdb_dir: Option<PathBuf>,
+    pub debug: bool,
+}
+
+impl SolveConfig {
+    pub fn new(algorithm: Algorithm) -> Self {
+        Self {
+            algorithm,
+            heuristic: algorithm.default_heuristic(),
+            heuristic_threshold: f32::MAX,
+            weight: algorithm.default_weight(),
+            limits: SolveLimits::default(),
+            pdb_dir: None,
+            debug: false,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), PuzzleError> {
+        if self.algorithm.needs_admissible_heuristic() && !self.heuristic.is_admissible() {
+            return Err(PuzzleError::InadmissibleHeuristic(format!("{} with {}", self.algorithm, self.heuristic)));
+        }
+        if !(self.weight >= 1. && self.weight.is_finite()) {
+            return Err(PuzzleError::InvalidWeight(self.weight));
+        }
+        Ok(())
+    }
+
+    /// Identifies the solver in cached answers, e.g. `ida/manhattan` or `wastar:2/linear-conflict`
+    pub fn name(&self) -> String {
+        if self.algorithm.is_weighted() {
+            format!("{}:{}/{}", self.algorithm, self.weight, self.heuristic)
+        } else {
+            format!("{}/{}", self.algorithm, self.heuristic)
+        }
+    }
+}
+
+
+/// One side of the bidirectional search. Boards reached are keyed by the whole packed board, so
+/// two boards sharing a Zobrist hash never meet by mistake, and each keeps only a pointer to its
+/// parent.
+struct SearchSide {
+    tree: SearchTree,
+    reached: FxHashMap<PackedState, u32>,
+}
+
+impl SearchSide {
+    fn new(root: PackedState) -> Self {
+        Self { tree: SearchTree::new(root), reached: FxHashMap::from_iter([(root, SearchTree::ROOT)]) }
+    }
+
+    /// Joins the route to `idx` on this side, walked from the start board, with the route to
+    /// `reverse_idx` walked from the solved board. Reverse moves are replayed backwards and
+    /// inverted.
+    fn join(&self, idx: u32, reverse: &SearchSide, reverse_idx: u32) -> Vec<Direction> {
+        self.tree.path(idx).into_iter()
+            .chain(reverse.tree.path(reverse_idx).into_iter().rev().map(|d| d.inverse()))
+            .collect()
+    }
+}
+
+/// A* open list entry as (f, h, node). The heap pops the lowest f, breaking ties on the lowest h
+/// and then on the node pushed last, which keeps the search diving towards the goal.
+type OpenEntry = Reverse<(u64, u32, Reverse<u32>)>;
+
+/// Weights on h are applied in thousandths, so f stays an integer
+const WEIGHT_SCALE: u64 = 1000;
+
+/// The open list entry for a node, f being `g + weight * h` in thousandths
+fn open_entry(g: u32, h: u32, weight: f32, idx: u32) -> OpenEntry {
+    let weight = (weight as f64 * WEIGHT_SCALE as f64).round() as u64;
+    Reverse((g as u64 * WEIGHT_SCALE + weight * h as u64, h, Reverse(idx)))
+}
+
+#[allow(clippy::ptr_arg)]
 pub fn serialize_moves(moves: &Vec<Direction>) -> String {
     moves.iter().map(|d| d.to_char()).collect()
 }
 
-#[derive(Clone, Debug, Eq, Ord, PartialOrd, PartialEq, Hash)]
+/// Reads moves written by `serialize_moves`
+pub fn parse_moves(moves: &str) -> Result<Vec<Direction>, PuzzleError> {
+    moves.chars().enumerate()
+        .map(|(idx, char)| Direction::from_char(char).ok_or(PuzzleError::UnknownMove(idx, char)))
+        .collect()
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
 pub struct Puzzle {
     pub(crate) width: u32,
     pub(crate) height: u32,
@@ -93,89 +321,122 @@ pub struct Puzzle {
     g: u32,
 }
 
-const RADIX: u32 = 10;
-impl Puzzle {
-    pub fn from_str(str: &str) -> Self {
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParseField {
+    Width,
+    Height,
+    Tiles,
+}
 
-        let width= str.chars().nth(0).unwrap().to_digit(RADIX).unwrap();
-        let height= str.chars().nth(2).unwrap().to_digit(RADIX).unwrap();
+/// Why a `width,height,tiles` line was rejected, with the character offset into the line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub field: ParseField,
+    pub offset: usize,
+    pub message: String,
+}
 
-        let mut tiles: Vec<Tile> = Vec::with_capacity((width * height) as usize);
+impl ParseError {
+    fn new(field: ParseField, offset: usize, message: impl Into<String>) -> Self {
+        Self { field, offset, message: message.into() }
+    }
+}
 
-        for char in str.chars().skip(4) {
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let field = match self.field {
+            ParseField::Width => "width",
+            ParseField::Height => "height",
+            ParseField::Tiles => "tiles",
+        };
+        write!(f, "Invalid {} at offset {}: {}", field, self.offset, self.message)
+    }
+}
 
-            let tile = Tile::new(char);
-            tiles.push(tile);
-        }
+impl std::error::Error for ParseError {}
 
+/// Tile labels in solved order, the blank and walls aside
+pub(crate) const LABELS: &str = "123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
 
-        let hash = hash_tiles(&tiles);
+impl FromStr for Puzzle {
+    type Err = ParseError;
 
-        Self {
-            tiles,
-            width,
-            height,
-            moves: vec![],
-            hash,
-            g: 0,
+    /// Parses `width,height,tiles`. Tiles must fill the board exactly, contain a single blank and
+    /// never repeat a label. The labels must also be the first ones in order with no gaps,
+    /// counting only open cells, or number the board by cell and skip the labels walls sit on.
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        fn dimension(field: ParseField, value: Option<&str>, offset: usize) -> Result<u32, ParseError> {
+            let value = value.ok_or_else(|| ParseError::new(field, offset, "missing"))?;
+            match value.parse::<u32>() {
+                Ok(dimension) if dimension > 0 => Ok(dimension),
+                _ => Err(ParseError::new(field, offset, format!("{:?} is not a positive number", value))),
+            }
         }
-    }
-
-    fn inverse_manhattan_distance(&self, tile: &Tile, root_puzzle: &Puzzle) -> u32 {
 
-        if self.hash == root_puzzle.hash {
-            return 0
-        }
+        let mut parts = str.splitn(3, ',');
 
-        if tile.rune == WALL {
-            return 0;  // Walls themselves have no "goal" distance
-        }
+        let width_str = parts.next();
+        let width = dimension(ParseField::Width, width_str, 0)?;
 
-        let idx = self.tiles.iter().position(|t| t.raw == tile.raw).unwrap();
-        let root_idx = root_puzzle.tiles.iter().position(|t| t.raw == tile.raw).unwrap();
-        let current_row = idx as u32 / self.width;
-        let current_col = idx as u32 % self.width;
-        let original_row = root_idx as u32 / self.width;
-        let original_col = root_idx as u32 % self.width;
+        let height_offset = width_str.map_or(0, |w| w.len() + 1);
+        let height_str = parts.next();
+        let height = dimension(ParseField::Height, height_str, height_offset)?;
 
-        let mut lateral_moves = (current_col as i32 - original_col as i32).abs() as u32;
-        let mut vertical_moves = (current_row as i32 - original_row as i32).abs() as u32;
+        let tiles_offset = height_offset + height_str.map_or(0, |h| h.len() + 1);
+        let tiles_str = parts.next().ok_or_else(|| ParseError::new(ParseField::Tiles, tiles_offset, "missing"))?;
 
-        if current_col != original_col {
-            let step = if original_col > current_col { 1 } else { - 1 };
-            for col in (current_col as i32..original_col as i32).step_by(step as usize) {
-                let check_idx = (current_row * self.width + col as u32) as usize;
-                if self.tiles[check_idx].rune == WALL {
-                    lateral_moves += 2
-                }
-            }
+        let expected = width.saturating_mul(height) as usize;
+        let found = tiles_str.chars().count();
+        if found != expected {
+            return Err(ParseError::new(ParseField::Tiles, tiles_offset, format!("expected {}x{} = {} tiles, found {}", width, height, expected, found)));
         }
 
-        if current_row != original_row {
-            let step = if original_row > current_row { 1 } else { -1 };
+        let mut tiles: Vec<Tile> = Vec::with_capacity(expected);
+        let mut has_space = false;
 
-            for row in (current_row as i32..original_row as i32).step_by(step as usize) {
-                let check_idx = (row as u32 * self.width + current_col) as usize;
-                if self.tiles[check_idx].rune == WALL {
-                    vertical_moves += 2;
-                }
-            }
-        }
+        for (idx, char) in tiles_str.chars().enumerate() {
+            let offset = tiles_offset + idx;
 
+            match char {
+                '0' if has_space => return Err(ParseError::new(ParseField::Tiles, offset, "second blank '0'")),
+                '0' => has_space = true,
+                '=' => {},
+                _ if !LABELS.contains(char) => return Err(ParseError::new(ParseField::Tiles, offset, format!("{:?} is not a tile label", char))),
+                _ if tiles.iter().any(|t| t.raw == char) => return Err(ParseError::new(ParseField::Tiles, offset, format!("duplicate tile '{}'", char))),
+                _ => {},
+            }
 
-        lateral_moves + vertical_moves
-    }
+            tiles.push(Tile::new(char));
+        }
 
-    fn get_inverse_heuristic(&self, root_puzzle: &Puzzle) -> u32 {
-        let mut score: u32 = 0;
+        if !has_space {
+            return Err(ParseError::new(ParseField::Tiles, tiles_offset, "no blank '0'"));
+        }
 
-        for tile in &self.tiles {
-            score+=self.inverse_manhattan_distance(tile, root_puzzle);
+        // Labels either count the open cells, or number every cell with walls skipping theirs.
+        // The last open cell is the blank's goal and has no tile.
+        let values = tiles.iter().filter(|t| t.rune == VALUE).count();
+        let in_order: Vec<char> = LABELS.chars().take(values).collect();
+        let by_cell: Vec<char> = LABELS.chars().zip(&tiles).filter(|(_, t)| t.rune != WALL).map(|(label, _)| label).take(values).collect();
+        let missing = |labels: &[char]| labels.iter().copied().find(|&label| tiles.iter().all(|t| t.raw != label));
+        if let (Some(missing), Some(_)) = (missing(&in_order), missing(&by_cell)) {
+            return Err(ParseError::new(ParseField::Tiles, tiles_offset, format!("labels are not contiguous, '{}' is missing", missing)));
         }
 
-        score
+        let hash = hash_tiles(&tiles);
+
+        Ok(Self {
+            tiles,
+            width,
+            height,
+            moves: vec![],
+            hash,
+            g: 0,
+        })
     }
+}
 
+impl Puzzle {
     pub fn manhattan_distance(&self, tile: &Tile, solved_puzzle: &Puzzle) -> u32 {
         if tile.rune == WALL {
             return 0;  // Walls themselves have no "goal" distance
@@ -183,6 +444,7 @@ impl Puzzle {
 
         let idx = self.tiles.iter().position(|t| t.raw == tile.raw).unwrap();
 
+        #[allow(unused_assignments)]
         let mut solved_idx: usize = 0;
 
         if let Some(tile_solved_idx) = tile.solved_idx {
@@ -191,6 +453,12 @@ impl Puzzle {
             solved_idx = solved_puzzle.tiles.iter().position(|t| t.rank() == tile.rank()).unwrap();
         }
 
+        self.legacy_distance(idx, solved_idx)
+    }
+
+    /// The distance `manhattan_distance` reports for a tile at `idx` bound for `solved_idx`
+    #[allow(clippy::cast_abs_to_unsigned)]
+    pub(crate) fn legacy_distance(&self, idx: usize, solved_idx: usize) -> u32 {
         let current_row = idx as u32 / self.width;
         let current_col = idx as u32 % self.width;
         let solved_row = solved_idx as u32 / self.width;
@@ -222,56 +490,82 @@ impl Puzzle {
         lateral_moves + vertical_moves
     }
 
-    fn linear_conflicts(&self, tile_idx: usize) -> u32 {
-        let tile = self.tiles.get(tile_idx).unwrap();
-
-        let mut linear_conflicts = 0;
-
-        if let Some(right) = self.tiles.get(tile_idx + 1) {
-            let is_reversal = (tile.rank() - right.rank()).abs() == 1;
-            if is_reversal {
-                linear_conflicts+=1;
+    /// Goal cell of every tile rank, indexed by `Tile::rank`. Ranks missing from the board hold
+    /// `usize::MAX`. Call on the solved board.
+    pub(crate) fn goal_cells(&self) -> [usize; 64] {
+        let mut goals = [usize::MAX; 64];
+        for (idx, tile) in self.tiles.iter().enumerate() {
+            if tile.rune == VALUE {
+                goals[tile.rank() as usize] = idx;
             }
         }
+        goals
+    }
 
-        if tile_idx >= 1 {
-            if let Some(left) = self.tiles.get(tile_idx - 1) {
-                let is_reversal = (tile.rank() - left.rank()).abs() == 1;
-                if is_reversal {
-                    linear_conflicts+=1;
-                }
-            }
-        }
+    /// Number of tiles that have to step out of their goal row or column so the other tiles in
+    /// that line can pass each other. Every such tile costs 2 moves on top of its manhattan
+    /// distance.
+    pub(crate) fn linear_conflicts(&self, goals: &[usize; 64]) -> u32 {
+        let width = self.width as usize;
+        let height = self.height as usize;
+        let code = |idx: usize| tile_code(&self.tiles[idx]);
+
+        let rows: u32 = (0..height).map(|row| Self::line_conflicts(&row_cells(row, width), goals, code)).sum();
+        let columns: u32 = (0..width).map(|col| Self::line_conflicts(&column_cells(col, width, height), goals, code)).sum();
+
+        rows + columns
+    }
 
-        if tile_idx >= self.width as usize {
-            if let Some(up) = self.tiles.get(tile_idx - self.width as usize) {
-                let is_reversal = (tile.rank() - up.rank()).abs() == 1;
-                if is_reversal {
-                    linear_conflicts+=1;
+    /// Conflicts within one row or column, `cells` listed in order and `code` giving the tile
+    /// code (see `state::tile_code`) in a cell. Walls split the line into segments and only
+    /// tiles whose goal lies in their current segment take part, a tile with a wall between it
+    /// and its goal already leaves the line to go around it, which the walking distances in
+    /// `distances` charge for.
+    pub(crate) fn line_conflicts(cells: &[usize], goals: &[usize; 64], code: impl Fn(usize) -> u8) -> u32 {
+        let mut conflicts = 0;
+
+        for segment in cells.split(|&idx| code(idx) == WALL_CODE) {
+            let mut order = [0; 7];
+            let mut len = 0;
+            for &idx in segment {
+                let code = code(idx);
+                if code == BLANK_CODE {
+                    continue;
+                }
+                if let Some(position) = segment.iter().position(|&cell| cell == goals[code as usize]) {
+                    order[len] = position;
+                    len += 1;
                 }
             }
+            conflicts += Self::removals(&order[..len]);
         }
 
+        conflicts
+    }
 
-
-        if let Some(down) = self.tiles.get(tile_idx + self.width as usize) {
-            let is_reversal = (tile.rank() - down.rank()).abs() == 1;
-
-            if is_reversal {
-                linear_conflicts+=1;
+    /// Fewest tiles to take out of a line so the rest are in goal order, which is the line
+    /// length minus its longest increasing run of goal positions.
+    fn removals(order: &[usize]) -> u32 {
+        let mut longest = [1; 7];
+        for i in 0..order.len() {
+            for j in 0..i {
+                if order[j] < order[i] {
+                    longest[i] = longest[i].max(longest[j] + 1);
+                }
             }
         }
 
-        linear_conflicts
+        (order.len() - longest[..order.len()].iter().copied().max().unwrap_or(0)) as u32
     }
 
     fn inversions(&self) -> u32 {
         let mut inversions = 0;
 
-        for (idx, tile) in self.tiles.iter().enumerate() {
-            let forward_tiles = &self.tiles[idx+1..self.tiles.len()];
+        // Walls never move, so they take no part in the permutation
+        let tiles: Vec<&Tile> = self.tiles.iter().filter(|t| t.rune != WALL).collect();
 
-            for forward_tile in forward_tiles {
+        for (idx, tile) in tiles.iter().enumerate() {
+            for forward_tile in &tiles[idx+1..] {
                 if forward_tile.rank() < tile.rank() {
                     inversions+=1;
                 }
@@ -281,14 +575,15 @@ impl Puzzle {
         inversions
     }
 
-    fn is_solved(&self, solved_puzzle: &Puzzle) -> bool {
+    pub(crate) fn is_solved(&self, solved_puzzle: &Puzzle) -> bool {
         self.hash == solved_puzzle.hash
     }
 
-    fn move_space(&mut self, dir: Direction) -> Result<(), PuzzleError> {
+    #[allow(clippy::manual_is_multiple_of)]
+    pub(crate) fn move_space(&mut self, dir: Direction) -> Result<(), PuzzleError> {
         let space_idx = self.space_idx();
 
-        match dir {
+        let target_idx = match dir {
             Direction::UP => {
                 let target_idx = space_idx.checked_sub(self.width as usize)
                     .ok_or(PuzzleError::IllegalMove("Cannot move up from top edge".into()))?;
@@ -296,8 +591,7 @@ impl Puzzle {
                 if self.tiles[target_idx].rune == WALL {
                     return Err(PuzzleError::IllegalMove("Cannot move space up".into()));
                 }
-                self.tiles.swap(space_idx, target_idx);
-                self.moves.push(Direction::UP);
+                target_idx
             },
             Direction::DOWN => {
                 let target_idx = space_idx + self.width as usize;
@@ -308,8 +602,7 @@ impl Puzzle {
                 if self.tiles[target_idx].rune == WALL {
                     return Err(PuzzleError::IllegalMove("Cannot move space down".into()));
                 }
-                self.tiles.swap(space_idx, target_idx);
-                self.moves.push(Direction::DOWN);
+                target_idx
             },
             Direction::LEFT => {
                 let target_idx = space_idx.checked_sub(1)
@@ -321,8 +614,7 @@ impl Puzzle {
                 if self.tiles[target_idx].rune == WALL {
                     return Err(PuzzleError::IllegalMove("Cannot move space left".into()));
                 }
-                self.tiles.swap(space_idx, target_idx);
-                self.moves.push(Direction::LEFT);
+                target_idx
             },
             Direction::RIGHT => {
                 let target_idx = space_idx + 1;
@@ -332,114 +624,269 @@ impl Puzzle {
                 if self.tiles[target_idx].rune == WALL {
                     return Err(PuzzleError::IllegalMove("Cannot move space right".into()));
                 }
-                self.tiles.swap(space_idx, target_idx);
-                self.moves.push(Direction::RIGHT);
+                target_idx
             },
-        }
+        };
 
-        self.hash = hash_tiles(&self.tiles);
+        // Only two cells change, so the hash is updated rather than recomputed
+        self.hash ^= zobrist_move(space_idx, target_idx, tile_code(&self.tiles[target_idx]));
+        self.tiles.swap(space_idx, target_idx);
+        self.moves.push(dir);
 
         Ok(())
     }
 
+    #[cfg(test)]
     pub fn moves_str(&self) -> String {
         self.moves.iter().map(|d| d.to_char()).collect()
     }
 
-    pub fn generate_successors(&self, space_idx: usize, step: u32) -> Successors {
-        let mut successors = Successors {
-            puzzles: unsafe { MaybeUninit::uninit().assume_init() },
-            count: 0,
-        };
+    /// Moves worth trying from this board: the legal ones, less the one that would undo the
+    /// previous move. Walking a single board with `move_space` and `undo_move` never allocates
+    /// per successor.
+    #[cfg(test)]
+    pub fn successor_moves(&self) -> Moves {
+        let mut moves = self.legal_moves(self.space_idx());
+        if let Some(&last) = self.moves.last() {
+            moves.remove(last.inverse());
+        }
+        moves
+    }
+
+    /// Takes back the last move, returning it
+    #[cfg(test)]
+    pub fn undo_move(&mut self) -> Option<Direction> {
+        let dir = self.moves.pop()?;
+        self.move_space(dir.inverse()).expect("the blank can always move back");
+        self.moves.pop();
+        Some(dir)
+    }
+
+    pub fn is_solvable(&self) -> bool {
+        self.check_solvable().is_ok()
+    }
+
+    /// Rejects boards that can never reach `solved()`.
+    ///
+    /// Open cells are grouped into regions separated by walls. Tiles outside the blank's region
+    /// can never move and must already be home, and tiles inside it must have their goal there
+    /// too. A dead end only ever gives its tile to the blank and takes it straight back, so dead
+    /// ends, and the cells left as dead ends once those are set aside, must already hold their
+    /// goal tile; the blank's cell and its goal cell are never set aside. If what is left is a
+    /// single corridor or a single loop, tiles can only shift along it and their order is checked
+    /// directly. Everything else falls back to the permutation parity, which must match the
+    /// parity of the blank's distance to its goal because every move swaps the blank with one
+    /// tile.
+    pub fn check_solvable(&self) -> Result<(), PuzzleError> {
+        let solved_puzzle = self.solved();
+        let space_idx = self.space_idx();
+        let solved_space_idx = solved_puzzle.space_idx();
+
+        let mut region = vec![usize::MAX; self.tiles.len()];
+        let mut regions: Vec<Vec<usize>> = vec![];
+
+        for start in 0..self.tiles.len() {
+            if self.tiles[start].rune == WALL || region[start] != usize::MAX {
+                continue;
+            }
+
+            let id = regions.len();
+            let mut cells = vec![start];
+            region[start] = id;
+
+            let mut next = 0;
+            while next < cells.len() {
+                for neighbour in self.open_neighbours(cells[next]) {
+                    if region[neighbour] == usize::MAX {
+                        region[neighbour] = id;
+                        cells.push(neighbour);
+                    }
+                }
+                next += 1;
+            }
+
+            regions.push(cells);
+        }
+
+        let space_region = region[space_idx];
+
+        if region[solved_space_idx] != space_region {
+            return Err(PuzzleError::UnsolvableBoard("the blank is walled off from its goal cell".into()));
+        }
 
-        // Get legal moves as before
-        for move_ in self.legal_moves(space_idx).into_iter() {
-            let mut successor = self.clone();
-            if successor.move_space(move_).is_ok() {
-                let mut hasher = FxHasher::default();
-                for tile in &successor.tiles {
-                    tile.hash(&mut hasher);
+        for (idx, tile) in self.tiles.iter().enumerate() {
+            if tile.rune != VALUE {
+                continue;
+            }
+
+            if region[idx] != space_region {
+                if solved_puzzle.tiles[idx].raw != tile.raw {
+                    return Err(PuzzleError::UnsolvableBoard(format!("tile '{}' is sealed off from the blank by walls", tile.raw)));
                 }
-                successor.hash = hasher.finish();
-                successor.g += step;
+                continue;
+            }
 
-                // SAFETY: We know this index is valid because count < 4
-                unsafe {
-                    successors.puzzles[successors.count].write(successor);
+            let goal_idx = solved_puzzle.tiles.iter().position(|t| t.raw == tile.raw).unwrap();
+            if region[goal_idx] != space_region {
+                return Err(PuzzleError::UnsolvableBoard(format!("tile '{}' would have to cross a wall to reach its goal", tile.raw)));
+            }
+        }
+
+        // Open neighbours of every cell that has not been set aside as a dead end
+        let mut degree = vec![0; self.tiles.len()];
+        for &idx in &regions[space_region] {
+            degree[idx] = self.open_neighbours(idx).count();
+        }
+        let peelable = |idx: usize, degree: usize| degree == 1 && idx != space_idx && idx != solved_space_idx;
+        let mut fixed = vec![false; self.tiles.len()];
+        let mut dead_ends: Vec<usize> = regions[space_region].iter().copied().filter(|&idx| peelable(idx, degree[idx])).collect();
+
+        while let Some(idx) = dead_ends.pop() {
+            fixed[idx] = true;
+            if self.tiles[idx].raw != solved_puzzle.tiles[idx].raw {
+                return Err(PuzzleError::UnsolvableBoard(format!("tile '{}' is stuck in a dead end away from its goal", self.tiles[idx].raw)));
+            }
+            for neighbour in self.open_neighbours(idx) {
+                if !fixed[neighbour] {
+                    degree[neighbour] -= 1;
+                    if peelable(neighbour, degree[neighbour]) {
+                        dead_ends.push(neighbour);
+                    }
                 }
+            }
+        }
 
-                successors.count += 1;
+        let cells: Vec<usize> = regions[space_region].iter().copied().filter(|&idx| !fixed[idx]).collect();
+        let degrees: Vec<usize> = cells.iter().map(|&idx| degree[idx]).collect();
+        let edges = degrees.iter().sum::<usize>() / 2;
+
+        if degrees.iter().all(|&d| d <= 2) {
+            // Corridor or loop: walk it in order and compare the tile sequences
+            let start = cells.iter().zip(&degrees).find(|(_, &d)| d < 2).map_or(cells[0], |(&idx, _)| idx);
+            let mut walk = vec![start];
+            let mut previous = usize::MAX;
+
+            while walk.len() < cells.len() {
+                let current = *walk.last().unwrap();
+                let next = self.open_neighbours(current).find(|&n| n != previous && !fixed[n]).unwrap();
+                previous = current;
+                walk.push(next);
             }
+
+            let sequence = |puzzle: &Puzzle| -> Vec<char> {
+                walk.iter().map(|&idx| puzzle.tiles[idx]).filter(|t| t.rune == VALUE).map(|t| t.raw).collect()
+            };
+            let current = sequence(self);
+            let goal = sequence(&solved_puzzle);
+
+            if edges < cells.len() {
+                if current != goal {
+                    return Err(PuzzleError::UnsolvableBoard("tiles in a dead-end corridor cannot pass each other".into()));
+                }
+            } else {
+                let rotated = (0..current.len().max(1)).any(|shift| {
+                    current.iter().cycle().skip(shift).take(current.len()).eq(goal.iter())
+                });
+                if !rotated {
+                    return Err(PuzzleError::UnsolvableBoard("tiles on a closed loop can only rotate around it".into()));
+                }
+            }
+        }
+
+        let space_distance = (space_idx / self.width as usize).abs_diff(solved_space_idx / self.width as usize)
+            + (space_idx % self.width as usize).abs_diff(solved_space_idx % self.width as usize);
+
+        if self.inversions() % 2 != space_distance as u32 % 2 {
+            return Err(PuzzleError::UnsolvableBoard(format!(
+                "tile permutation has {} inversions but the blank is {} moves from its goal, parity differs",
+                self.inversions(), space_distance
+            )));
         }
 
-        successors
+        Ok(())
     }
 
-    fn is_solvable(&self) -> bool {
-        // let
-        // let inversions = self.inversions();
-        // if inversions % 2 != 0 {
-        //     println!("Puzzle is unsolvable: {}", inversions)
-        // }
-        true
+    fn open_neighbours(&self, idx: usize) -> impl Iterator<Item = usize> + '_ {
+        let width = self.width as usize;
+        let (row, col) = (idx / width, idx % width);
+
+        [
+            (row > 0).then(|| idx - width),
+            (row + 1 < self.height as usize).then(|| idx + width),
+            (col > 0).then(|| idx - 1),
+            (col + 1 < width).then(|| idx + 1),
+        ]
+            .into_iter()
+            .flatten()
+            .filter(|&neighbour| self.tiles[neighbour].rune != WALL)
     }
 
-    pub fn solve(&mut self, debug: bool, heuristic_threshold: f32) -> Option<Vec<Direction>> {
-        const STEP: u32 = 1;
-        const MAX_ITERATIONS: usize = 1000000000;
+    /// A* over packed states. Nodes live in a `SearchTree` and only remember their parent and
+    /// last move, the full move list is rebuilt once the solved board comes off the open list.
+    /// Weighted A* runs the same search ordered by g + w·h.
+    fn astar(&mut self, config: &SolveConfig, budget: &mut Budget) -> SolveOutcome {
+        let SolveConfig { debug, heuristic_threshold, .. } = *config;
+        let weight = if config.algorithm == Algorithm::WeightedAStar { config.weight } else { 1. };
         let solved_puzzle = self.solved();
-
-        let mut open_list = BinaryHeap::<(Reverse<OrderedFloat<f32>>, Puzzle)>::new();
+        let heuristic = config.heuristic.prepare(&solved_puzzle, config.pdb_dir.as_deref());
+
+        let width = self.width as usize;
+        let cells = self.tiles.len();
+        let goal = PackedState::from_puzzle(&solved_puzzle);
+
+        let root = PackedState::from_puzzle(self);
+        let mut tree = SearchTree::new(root);
+        // Estimate and heuristic memo of every node in `tree`, children are scored from these
+        let mut memo = Memo::default();
+        let mut estimates = vec![(heuristic.evaluate(&root, &mut memo), memo)];
+        let mut open_list = BinaryHeap::<OpenEntry>::new();
+        // Keyed by the whole board, two boards sharing a Zobrist hash must not close each other
         let mut closed_list = FxHashSet::default();
+        // Only unpacked for debug output
+        let mut scratch = self.clone();
 
-        let space_idx = self.space_idx();
-
-        // Initial successors
-        for neighbour in self.generate_successors(space_idx, STEP) {
-            let new_cost = self.g + STEP;
-            let heuristic = OrderedFloat(new_cost as f32 + neighbour.get_heuristic(&solved_puzzle));
-            open_list.push((Reverse(heuristic), neighbour));
-        }
+        open_list.push(open_entry(0, estimates[0].0, weight, SearchTree::ROOT));
 
-        let mut iteration = 0;
-        while let Some((Reverse(_heuristic), puzzle)) = open_list.pop() {
-            iteration += 1;
-            if iteration > MAX_ITERATIONS {
-                break;
-            }
+        while let Some(Reverse((_f, _h, Reverse(idx)))) = open_list.pop() {
+            let node = *tree.get(idx);
+            let (estimate, memo) = estimates[idx as usize];
 
             if debug {
-                puzzle.debug_print(false);
+                node.state.write_tiles(&mut scratch.tiles);
+                scratch.debug_print(false);
                 println!("states visited: {}", closed_list.len());
-                println!("Heuristic: {}", puzzle.get_heuristic(&solved_puzzle));
+                println!("Heuristic: {}", estimate);
             }
 
-            if puzzle.is_solved(&solved_puzzle) {
-                *self = puzzle.clone();
-                return Some(self.moves.to_vec());
+            if node.state == goal {
+                return SolveOutcome::Solved(self.replay(&tree.path(idx)));
             }
 
-            if closed_list.insert(puzzle.hash) {
-                let space_idx = puzzle.space_idx();
-                let successors = puzzle.generate_successors(space_idx, STEP);
+            if closed_list.insert(node.state) {
+                if let Err(stop) = budget.expand(tree.len()) {
+                    return stop.into();
+                }
+
+                for dir in Direction::ALL {
+                    let Some(next) = node.state.apply(dir, width, cells) else {
+                        continue;
+                    };
+                    if closed_list.contains(&next) {
+                        continue;
+                    }
 
-                // Process successors in parallel and collect results
-                let new_states: Vec<_> = successors.par_bridge().into_par_iter().filter_map(|neighbour| {
-                    let g = puzzle.g + STEP;
-                    let h = neighbour.get_heuristic(&solved_puzzle);
-                    let heuristic = OrderedFloat(g as f32 + h);
+                    let mut next_memo = memo;
+                    let next_estimate = heuristic.update(&node.state, &next, estimate, &mut next_memo);
+                    let f = node.g + 1 + next_estimate;
 
                     // If the heuristic is too high, skip this state
-                    if heuristic.0 < heuristic_threshold {
-                        Some((Reverse(heuristic), neighbour))
+                    if (f as f32) < heuristic_threshold {
+                        let next_idx = tree.push(next, idx, dir);
+                        open_list.push(open_entry(node.g + 1, next_estimate, weight, next_idx));
+                        estimates.push((next_estimate, next_memo));
                     } else {
-                        None
+                        budget.prune();
                     }
-                }).collect();
-
-                // Add all new states to the open list
-                for state in new_states {
-                    open_list.push(state);
                 }
             }
 
@@ -447,74 +894,343 @@ impl Puzzle {
                 sleep(std::time::Duration::from_millis(5));
             }
         }
-        None
-    }
 
-    fn serialized(&self) -> String {
-        let tiles_str: String = self.tiles.iter().map(|t| t.raw).collect();
+        budget.exhausted()
+    }
 
-        format!("{},{},{}", self.width, self.height, tiles_str)
+    /// Plays a found route onto the board, returning every move made so far
+    fn replay(&mut self, moves: &[Direction]) -> Vec<Direction> {
+        for &dir in moves {
+            self.move_space(dir).expect("search routes only make legal moves");
+        }
+        self.g += moves.len() as u32;
+        self.moves.to_vec()
     }
 
-    fn space_idx(&self) -> usize {
-        self.tiles.iter().position(|t| { t.rune == SPACE }).unwrap()
+    /// Runs the configured algorithm within `config.limits`, reporting how the search ended
+    /// and what it cost. Only a config that cannot be run is an error.
+    pub fn search(&mut self, config: &SolveConfig) -> Result<SolveResult, PuzzleError> {
+        config.validate()?;
+        let mut budget = Budget::new(config.limits.clone());
+
+        let outcome = if !self.is_solvable() {
+            SolveOutcome::Unsolvable
+        } else {
+            match config.algorithm {
+                Algorithm::AStar | Algorithm::WeightedAStar => self.astar(config, &mut budget),
+                Algorithm::IdaStar => self.ida_star(config, &mut budget),
+                Algorithm::Bidirectional => self.bidirectional(config, &mut budget),
+                Algorithm::AraStar => self.ara_star(config, &mut budget),
+                Algorithm::Reduction => self.reduction(config, &mut budget),
+            }
+        };
+
+        Ok(budget.finish(outcome))
     }
 
-    fn legal_moves(&self, space_idx: usize) -> Vec<Direction> {
-        let mut legal_moves: Vec<Direction> = Vec::with_capacity(4);
+    /// Anytime repairing A* (ARA*). A weighted A* pass with the configured weight finds a first
+    /// answer quickly, then every pass lowers the weight by `ARA_WEIGHT_STEP` and repairs the
+    /// previous search instead of starting over: states whose g improved after they were
+    /// expanded are put back on the open list, everything else keeps its g. Each improved answer
+    /// comes with a bound on how far from optimal it can be. The pass with weight 1 ends with an
+    /// optimal answer, unless the budget runs out first.
+    ///
+    /// Returns the answers in the order they were found, each shorter than the last, and why the
+    /// search stopped early if it did. The board is left as it is, `search` replays the last
+    /// answer onto it.
+    pub(crate) fn solve_anytime(&self, config: &SolveConfig, budget: &mut Budget) -> (Vec<AnytimeSolution>, Option<Stop>) {
+        const ARA_WEIGHT_STEP: f32 = 0.5;
 
-        legal_moves.push(Direction::UP);
-        legal_moves.push(Direction::DOWN);
-        legal_moves.push(Direction::LEFT);
-        legal_moves.push(Direction::RIGHT);
+        let solved_puzzle = self.solved();
+        let heuristic = config.heuristic.prepare(&solved_puzzle, config.pdb_dir.as_deref());
+
+        let width = self.width as usize;
+        let cells = self.tiles.len();
+        let goal = PackedState::from_puzzle(&solved_puzzle);
+
+        let root = PackedState::from_puzzle(self);
+        let mut tree = SearchTree::new(root);
+        let mut memo = Memo::default();
+        let mut estimates = vec![(heuristic.evaluate(&root, &mut memo), memo)];
+        // Node holding the lowest g found for every state, older nodes for the state are stale
+        let mut best = FxHashMap::from_iter([(root, SearchTree::ROOT)]);
+        let mut open_list = BinaryHeap::<OpenEntry>::new();
+        let mut closed_list = FxHashSet::default();
+        // Closed states whose g improved during the current pass
+        let mut inconsistent: Vec<u32> = vec![];
 
-        let row = space_idx / self.width as usize;
-        let col = space_idx % self.width as usize;
+        let mut weight = config.weight;
+        let mut incumbent = (root == goal).then_some(SearchTree::ROOT);
+        let mut solutions = vec![];
+        let mut stopped = None;
 
-        // Boundary checks
-        if row == 0 { // Top row
-            legal_moves.retain(|&d| d != Direction::UP);
-        }
-        if row == (self.height - 1) as usize { // Bottom row
-            legal_moves.retain(|&d| d != Direction::DOWN);
+        open_list.push(open_entry(0, estimates[0].0, weight, SearchTree::ROOT));
+
+        loop {
+            while let Some(&Reverse((f, _, Reverse(idx)))) = open_list.peek() {
+                // Nothing left on the open list can beat the answer under this weight
+                if incumbent.is_some_and(|goal_idx| f >= tree.get(goal_idx).g as u64 * WEIGHT_SCALE) {
+                    break;
+                }
+
+                open_list.pop();
+                let node = *tree.get(idx);
+                if best[&node.state] != idx || !closed_list.insert(node.state) {
+                    continue;
+                }
+                if let Err(stop) = budget.expand(tree.len()) {
+                    stopped = Some(stop);
+                    break;
+                }
+
+                let (estimate, memo) = estimates[idx as usize];
+                for dir in Direction::ALL {
+                    let Some(next) = node.state.apply(dir, width, cells) else {
+                        continue;
+                    };
+                    let g = node.g + 1;
+                    if best.get(&next).is_some_and(|&known| tree.get(known).g <= g) {
+                        continue;
+                    }
+
+                    let mut next_memo = memo;
+                    let next_estimate = heuristic.update(&node.state, &next, estimate, &mut next_memo);
+                    if ((g + next_estimate) as f32) >= config.heuristic_threshold {
+                        budget.prune();
+                        continue;
+                    }
+
+                    let next_idx = tree.push(next, idx, dir);
+                    estimates.push((next_estimate, next_memo));
+                    best.insert(next, next_idx);
+
+                    if next == goal {
+                        incumbent = Some(next_idx);
+                    }
+                    if closed_list.contains(&next) {
+                        inconsistent.push(next_idx);
+                    } else {
+                        open_list.push(open_entry(g, next_estimate, weight, next_idx));
+                    }
+                }
+            }
+
+            if let Some(goal_idx) = incumbent {
+                let length = tree.get(goal_idx).g;
+
+                // Every state that could still lead somewhere shorter is open or inconsistent,
+                // so the lowest g + h among them is a lower bound on the optimal length
+                let lower = open_list.iter()
+                    .map(|Reverse((_, _, Reverse(idx)))| *idx)
+                    .chain(inconsistent.iter().copied())
+                    .filter(|idx| best[&tree.get(*idx).state] == *idx)
+                    .map(|idx| tree.get(idx).g + estimates[idx as usize].0)
+                    .min();
+
+                let mut bound = lower.map_or(1., |lower| (length as f32 / lower.max(1) as f32).max(1.));
+                if stopped.is_none() {
+                    bound = bound.min(weight);
+                }
+
+                if solutions.last().is_none_or(|last: &AnytimeSolution| length < last.moves.len() as u32) {
+                    solutions.push(AnytimeSolution { moves: tree.path(goal_idx), bound, weight });
+                    if config.debug {
+                        println!("ARA* weight {}: {} moves, at most {:.3} times optimal", weight, length, bound);
+                    }
+                } else if let Some(last) = solutions.last_mut() {
+                    last.bound = last.bound.min(bound);
+                }
+            }
+
+            if stopped.is_some() || weight <= 1. || (open_list.is_empty() && inconsistent.is_empty()) {
+                break;
+            }
+
+            // Next pass: inconsistent states join the open list and everything is reordered
+            // under the lower weight
+            weight = (weight - ARA_WEIGHT_STEP).max(1.);
+            let pending: Vec<u32> = open_list.drain()
+                .map(|Reverse((_, _, Reverse(idx)))| idx)
+                .chain(inconsistent.drain(..))
+                .filter(|idx| best[&tree.get(*idx).state] == *idx)
+                .collect();
+            open_list.extend(pending.into_iter().map(|idx| open_entry(tree.get(idx).g, estimates[idx as usize].0, weight, idx)));
+            closed_list.clear();
         }
-        if col == 0 { // Left edge
-            legal_moves.retain(|&d| d != Direction::LEFT);
+
+        (solutions, stopped)
+    }
+
+    /// The best ARA* answer. When a limit or cancellation cut the later passes short it comes
+    /// with the stopped outcome instead, so callers know it may not be optimal.
+    fn ara_star(&mut self, config: &SolveConfig, budget: &mut Budget) -> SolveOutcome {
+        let (mut solutions, stopped) = self.solve_anytime(config, budget);
+        match (solutions.pop(), stopped) {
+            (Some(best), None) => SolveOutcome::Solved(self.replay(&best.moves)),
+            (best, Some(stop)) => stop.with_best(best.map(|best| self.replay(&best.moves))),
+            (None, None) => budget.exhausted(),
         }
-        if col == (self.width - 1) as usize { // Right edge
-            legal_moves.retain(|&d| d != Direction::RIGHT);
+    }
+
+    /// Leaves the board solved, as every other solver does
+    fn reduction(&mut self, config: &SolveConfig, budget: &mut Budget) -> SolveOutcome {
+        match reduction::solve(self, config, budget) {
+            SolveOutcome::Solved(moves) => SolveOutcome::Solved(self.replay(&moves)),
+            outcome => outcome,
         }
+    }
+
+    /// Iterative deepening A*. Only the current path is kept in memory, and the bound is raised
+    /// to the smallest f that exceeded it until a solution is found or the bound reaches the
+    /// threshold. The heuristic must be admissible so the returned moves are optimal.
+    fn ida_star(&mut self, config: &SolveConfig, budget: &mut Budget) -> SolveOutcome {
+        let solved_puzzle = self.solved();
+        let heuristic = config.heuristic.prepare(&solved_puzzle, config.pdb_dir.as_deref());
+
+        let mut search = IdaSearch {
+            heuristic: heuristic.as_ref(),
+            goal: PackedState::from_puzzle(&solved_puzzle),
+            width: self.width as usize,
+            cells: self.tiles.len(),
+            budget,
+            stopped: None,
+        };
+
+        let root = PackedState::from_puzzle(self);
+        let mut memo = Memo::default();
+        let estimate = heuristic.evaluate(&root, &mut memo);
+        let mut bound = self.g + estimate;
+        let mut path = vec![];
 
-        // Wall checks
-        if let Some(tile) = self.tiles.get(space_idx + 1) {
-            if tile.rune == WALL {
-                legal_moves.retain(|&d| d != Direction::RIGHT);
+        while (bound as f32) < config.heuristic_threshold {
+            if config.debug {
+                println!("IDA* bound: {}", bound);
             }
-        }
-        if space_idx >= 1 {
-            if let Some(tile) = self.tiles.get(space_idx - 1) {
-                if tile.rune == WALL {
-                    legal_moves.retain(|&d| d != Direction::LEFT);
-                }
+
+            match search.search(&root, self.g, estimate, memo, bound, &mut path) {
+                Ok(()) => return SolveOutcome::Solved(self.replay(&path)),
+                Err(None) => return search.stopped.map_or(SolveOutcome::Unsolvable, SolveOutcome::from),
+                Err(Some(next_bound)) => bound = next_bound,
             }
         }
-        if let Some(tile) = self.tiles.get(space_idx + self.width as usize) {
-            if tile.rune == WALL {
-                legal_moves.retain(|&d| d != Direction::DOWN);
-            }
+
+        SolveOutcome::BudgetExhausted(Limit::Threshold, None)
+    }
+
+    /// Breadth-first search from the start board and the solved board at the same time, always
+    /// growing the smaller frontier by one layer. The search stops on the layer where the two
+    /// sides first reach the same board, and the shortest joined route is replayed onto `self`.
+    /// The threshold caps the total number of moves.
+    fn bidirectional(&mut self, config: &SolveConfig, budget: &mut Budget) -> SolveOutcome {
+        let SolveConfig { debug, heuristic_threshold, .. } = *config;
+        let solved_puzzle = self.solved();
+
+        if self.is_solved(&solved_puzzle) {
+            return SolveOutcome::Solved(self.moves.to_vec());
         }
-        if space_idx >= self.width as usize {
-            if let Some(tile) = self.tiles.get(space_idx - self.width as usize) {
-                if tile.rune == WALL {
-                    legal_moves.retain(|&d| d != Direction::UP);
+
+        let width = self.width as usize;
+        let cells = self.tiles.len();
+        let mut forward = SearchSide::new(PackedState::from_puzzle(self));
+        let mut reverse = SearchSide::new(PackedState::from_puzzle(&solved_puzzle));
+
+        // Frontiers hold node indices into their side's tree
+        let mut forward_frontier = vec![SearchTree::ROOT];
+        let mut reverse_frontier = vec![SearchTree::ROOT];
+        let mut depth = 0;
+
+        while !forward_frontier.is_empty() && !reverse_frontier.is_empty() {
+            if (depth as f32) >= heuristic_threshold {
+                return SolveOutcome::BudgetExhausted(Limit::Threshold, None);
+            }
+
+            let (frontier, side, opposite, is_forward) = if forward_frontier.len() <= reverse_frontier.len() {
+                (&mut forward_frontier, &mut forward, &reverse, true)
+            } else {
+                (&mut reverse_frontier, &mut reverse, &forward, false)
+            };
+
+            let mut next_frontier = vec![];
+            let mut best: Option<Vec<Direction>> = None;
+
+            for idx in frontier.drain(..) {
+                if let Err(stop) = budget.expand(side.reached.len() + opposite.reached.len()) {
+                    return stop.into();
                 }
+
+                let node = *side.tree.get(idx);
+                for dir in Direction::ALL {
+                    // The parent is already reached, so undoing the last move is skipped here too
+                    let Some(state) = node.state.apply(dir, width, cells) else { continue };
+                    if side.reached.contains_key(&state) {
+                        continue;
+                    }
+
+                    let next = side.tree.push(state, idx, dir);
+
+                    if let Some(&meeting) = opposite.reached.get(&state) {
+                        let moves = if is_forward { side.join(next, opposite, meeting) } else { opposite.join(meeting, side, next) };
+                        if best.as_ref().is_none_or(|best| moves.len() < best.len()) {
+                            best = Some(moves);
+                        }
+                    }
+
+                    side.reached.insert(state, next);
+                    next_frontier.push(next);
+                }
+            }
+
+            *frontier = next_frontier;
+            depth += 1;
+
+            if debug {
+                println!("depth: {} states visited: {} forward, {} reverse", depth, forward.reached.len(), reverse.reached.len());
+            }
+
+            if let Some(moves) = best {
+                return SolveOutcome::Solved(self.replay(&moves));
             }
         }
 
+        SolveOutcome::Unsolvable
+    }
+
+    pub(crate) fn serialized(&self) -> String {
+        let tiles_str: String = self.tiles.iter().map(|t| t.raw).collect();
+
+        format!("{},{},{}", self.width, self.height, tiles_str)
+    }
+
+    pub(crate) fn space_idx(&self) -> usize {
+        self.tiles.iter().position(|t| { t.rune == SPACE }).unwrap()
+    }
+
+    #[cfg(test)]
+    pub(crate) fn legal_moves(&self, space_idx: usize) -> Moves {
+        let width = self.width as usize;
+        let row = space_idx / width;
+        let col = space_idx % width;
+        let open = |idx: usize| self.tiles[idx].rune != WALL;
+
+        let mut legal_moves = Moves::default();
+        if row > 0 && open(space_idx - width) {
+            legal_moves.push(Direction::UP);
+        }
+        if row + 1 < self.height as usize && open(space_idx + width) {
+            legal_moves.push(Direction::DOWN);
+        }
+        if col > 0 && open(space_idx - 1) {
+            legal_moves.push(Direction::LEFT);
+        }
+        if col + 1 < width && open(space_idx + 1) {
+            legal_moves.push(Direction::RIGHT);
+        }
+
         legal_moves
     }
 
 
+    #[allow(clippy::unnecessary_sort_by)]
     pub fn solved(&self) -> Puzzle {
         let mut solved_tiles = self.tiles.clone();
 
@@ -548,38 +1264,120 @@ impl Puzzle {
 
 }
 
-pub struct Successors {
-    puzzles: [MaybeUninit<Puzzle>; 4],
-    count: usize,
+/// What stays fixed during one IDA* run, so the recursion only passes the changing state
+struct IdaSearch<'a> {
+    heuristic: &'a dyn BoardHeuristic,
+    goal: PackedState,
+    width: usize,
+    cells: usize,
+    budget: &'a mut Budget,
+    /// Set when a limit or cancellation ended the search, which then unwinds as if the space
+    /// were exhausted
+    stopped: Option<Stop>,
 }
 
-impl Successors {
-    // Safe way to get a reference to a puzzle
-    pub fn get(&self, index: usize) -> Option<&Puzzle> {
-        if index < self.count {
-            // SAFETY: We know this index is initialized because count tracks valid puzzles
-            unsafe { Some(&*self.puzzles[index].as_ptr()) }
-        } else {
-            None
+impl IdaSearch<'_> {
+    /// Depth-first search below `bound`, leaving the moves to the goal in `path`. Fails with the
+    /// smallest f that went over the bound, or `None` when nothing did and the search space is
+    /// exhausted.
+    fn search(&mut self, state: &PackedState, g: u32, estimate: u32, memo: Memo, bound: u32, path: &mut Vec<Direction>) -> Result<(), Option<u32>> {
+        let f = g + estimate;
+        if f > bound {
+            return Err(Some(f));
         }
-    }
 
-    pub fn iter(&self) -> impl Iterator<Item = &Puzzle> {
-        (0..self.count).filter_map(|i| self.get(i))
+        if *state == self.goal {
+            return Ok(());
+        }
+
+        if let Err(stop) = self.budget.expand(path.len() + 1) {
+            self.stopped = Some(stop);
+            return Err(None);
+        }
+
+        // Undoing the previous move can never be part of an optimal path
+        let backtrack = path.last().map(|d| d.inverse());
+        let mut next_bound: Option<u32> = None;
+
+        for dir in Direction::ALL {
+            if Some(dir) == backtrack {
+                continue;
+            }
+            let Some(next) = state.apply(dir, self.width, self.cells) else {
+                continue;
+            };
+
+            let mut next_memo = memo;
+            let next_estimate = self.heuristic.update(state, &next, estimate, &mut next_memo);
+
+            path.push(dir);
+            match self.search(&next, g + 1, next_estimate, next_memo, bound, path) {
+                Ok(()) => return Ok(()),
+                Err(None) if self.stopped.is_some() => return Err(None),
+                Err(Some(f)) => next_bound = Some(next_bound.map_or(f, |next| next.min(f))),
+                Err(None) => {},
+            }
+            path.pop();
+        }
+
+        Err(next_bound)
     }
 }
 
-impl Iterator for Successors {
-    type Item = Puzzle;
+/// Up to four directions kept on the stack
+#[cfg(test)]
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct Moves {
+    dirs: [Option<Direction>; 4],
+    len: usize,
+}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if self.count > 0 {
-            // SAFETY: We know this index is initialized because count tracks valid puzzles
-            let puzzle = unsafe { self.puzzles[self.count - 1].as_ptr().read() };
-            self.count -= 1;
-            Some(puzzle)
-        } else {
-            None
+#[cfg(test)]
+impl Moves {
+    fn push(&mut self, dir: Direction) {
+        self.dirs[self.len] = Some(dir);
+        self.len += 1;
+    }
+
+    fn remove(&mut self, dir: Direction) {
+        if let Some(idx) = self.dirs[..self.len].iter().position(|&d| d == Some(dir)) {
+            self.dirs.copy_within(idx + 1..self.len, idx);
+            self.len -= 1;
+            self.dirs[self.len] = None;
         }
     }
-}
\ No newline at end of file
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+#[cfg(test)]
+impl std::ops::Index<usize> for Moves {
+    type Output = Direction;
+
+    fn index(&self, idx: usize) -> &Direction {
+        self.dirs[..self.len][idx].as_ref().unwrap()
+    }
+}
+
+#[cfg(test)]
+impl IntoIterator for Moves {
+    type Item = Direction;
+    type IntoIter = std::iter::Flatten<std::array::IntoIter<Option<Direction>, 4>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.dirs.into_iter().flatten()
+    }
+}
+
+#[cfg(test)]
+impl fmt::Debug for Moves {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(*self).finish()
+    }
+}
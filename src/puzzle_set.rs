@@ -0,0 +1,101 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::str::FromStr;
+use crate::budget::MoveCounts;
+use crate::puzzle::{ParseError, Puzzle};
+
+/// A downloaded puzzle file. The first line holds the move limits and the number of puzzles as
+/// `left right up down count`, followed by one `width,height,tiles` line per puzzle. A line that
+/// fails to parse keeps its slot so the remaining puzzles stay at their submission index.
+#[derive(Debug, Clone)]
+pub struct PuzzleSet {
+    pub limits: MoveCounts,
+    pub puzzles: Vec<Result<Puzzle, ParseError>>,
+}
+
+#[derive(Debug)]
+pub enum PuzzleSetError {
+    MissingFile(String),
+    Io(io::Error),
+    MalformedHeader(String),
+    CountMismatch { declared: usize, found: usize },
+}
+
+impl fmt::Display for PuzzleSetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PuzzleSetError::MissingFile(path) => write!(f, "Puzzle file {} does not exist, run fetch first", path),
+            PuzzleSetError::Io(e) => write!(f, "Could not read puzzle file: {}", e),
+            PuzzleSetError::MalformedHeader(msg) => write!(f, "Malformed puzzle file header: {}", msg),
+            PuzzleSetError::CountMismatch { declared, found } => write!(f, "Header declares {} puzzles but the file contains {}", declared, found),
+        }
+    }
+}
+
+impl std::error::Error for PuzzleSetError {}
+
+impl From<io::Error> for PuzzleSetError {
+    fn from(e: io::Error) -> Self {
+        PuzzleSetError::Io(e)
+    }
+}
+
+impl PuzzleSet {
+    pub fn load(path: &str) -> Result<Self, PuzzleSetError> {
+        let contents = fs::read_to_string(path).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => PuzzleSetError::MissingFile(path.into()),
+            _ => PuzzleSetError::Io(e),
+        })?;
+
+        Self::parse(&contents)
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, PuzzleSetError> {
+        let mut lines = contents.lines();
+        let header = lines.next().ok_or_else(|| PuzzleSetError::MalformedHeader("file is empty".into()))?;
+
+        let values = header.split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|value| !value.is_empty())
+            .map(|value| value.parse::<u32>().map_err(|_| PuzzleSetError::MalformedHeader(format!("{} is not a number", value))))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let (limits, count) = match values[..] {
+            [left, right, up, down, count] => (MoveCounts::new(up, down, left, right), count as usize),
+            _ => return Err(PuzzleSetError::MalformedHeader(format!("expected 5 values, got {}", values.len()))),
+        };
+
+        let puzzles: Vec<Result<Puzzle, ParseError>> = lines
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| Puzzle::from_str(line.trim()))
+            .collect();
+
+        if puzzles.len() != count {
+            return Err(PuzzleSetError::CountMismatch { declared: count, found: puzzles.len() });
+        }
+
+        Ok(Self { limits, puzzles })
+    }
+
+    pub fn len(&self) -> usize {
+        self.puzzles.len()
+    }
+
+    #[cfg(test)]
+    pub fn is_empty(&self) -> bool {
+        self.puzzles.is_empty()
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&Puzzle> {
+        self.puzzles.get(idx).and_then(|puzzle| puzzle.as_ref().ok())
+    }
+
+    #[cfg(test)]
+    pub fn get_mut(&mut self, idx: usize) -> Option<&mut Puzzle> {
+        self.puzzles.get_mut(idx).and_then(|puzzle| puzzle.as_mut().ok())
+    }
+
+    pub fn errors(&self) -> impl Iterator<Item = (usize, &ParseError)> {
+        self.puzzles.iter().enumerate().filter_map(|(idx, puzzle)| puzzle.as_ref().err().map(|e| (idx, e)))
+    }
+}
@@ -0,0 +1,500 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+use std::time::{Duration, Instant};
+use rustc_hash::FxHashMap;
+use crate::heuristic::HeuristicKind;
+use crate::limits::{Budget, Limit, SolveLimits, SolveOutcome, Stop};
+use crate::optimise::{optimise, DEFAULT_WINDOW};
+use crate::puzzle::{Algorithm, Direction, Puzzle, SolveConfig, LABELS};
+use crate::tile::{Rune, Tile};
+
+/// Open cells left when peeling stops and an exact search finishes the board, a 3x3 grid has
+/// 9!/2 states which A* covers in a moment
+const FINAL_CELLS: usize = 9;
+/// Tiles one placement can track, its keys hold the blank and every tile in 6 bits each, so
+/// this also bounds the cells fixed in one step
+const MAX_TRACKED: usize = 9;
+/// States one placement may visit before it counts as failed
+const MAX_PLACE_STATES: usize = 1_000_000;
+/// Time one placement may take before it counts as failed, a placement that cannot be done
+/// rarely runs out of states quickly on a large board
+const MAX_PLACE_TIME: Duration = Duration::from_secs(1);
+/// Weight on the walk home of the tiles being placed
+const PLACE_WEIGHT: u32 = 5;
+/// States the weighted A* search may hold when it takes over a region no peel could reduce
+const MAX_REGION_STATES: usize = 1_000_000;
+/// Single cells tried as a peel when no line can be peeled
+const MAX_SINGLE_PEELS: usize = 4;
+
+/// A set of cells, one bit per cell, boards have at most 49
+type Cells = u64;
+
+fn cells(set: Cells) -> impl Iterator<Item = usize> + Clone {
+    (0..64).filter(move |&idx| set >> idx & 1 == 1)
+}
+
+/// A line of cells about to be placed and fixed, split into the steps that place them
+struct Peel {
+    steps: Vec<Vec<usize>>,
+    /// Walls the open cells left afterwards close a loop around
+    holes: usize,
+    /// Walls inside the bounding box of the cells left afterwards
+    walls_left: usize,
+    /// Length of the side the peel shortens, 0 for a single cell
+    side: usize,
+}
+
+/// A board being reduced, with the cells whose tiles are home for good
+struct Reducer {
+    width: usize,
+    tiles: Vec<Tile>,
+    blank: usize,
+    /// Goal cell of every tile, by rank
+    goals: [usize; 64],
+    blank_goal: usize,
+    /// Walls, cells walled off from the blank, and cells already solved that later steps may
+    /// not disturb
+    fixed: Vec<bool>,
+    moves: Vec<Direction>,
+}
+
+/// Solves a board of any size quickly, though far from optimally: the top row or left column is
+/// solved and fixed, then the rest of the board is reduced the same way until at most
+/// `FINAL_CELLS` open cells are left, which an exact A* search finishes with `config.heuristic`.
+///
+/// Every step moves a few tiles home at once with a breadth-first search over the blank and those
+/// tiles alone, walking around walls and fixed cells. A step may never leave the open cells with
+/// a new cut cell, one whose loss splits them in two: no tile can pass one between two loops, and
+/// a cell left with a single open neighbour is the corner problem of the plain puzzle. Steps grow
+/// until they avoid that, so walls decide which cells go together. Which line goes next depends on
+/// the walls too: among the lines that can be split into such steps, the one leaving the fewest
+/// walls behind wins, then the one shortening the longer side, and single cells are the last
+/// resort. Bottom rows and right columns are never peeled, the blank finishes there.
+///
+/// Walls can leave the rest of the board unsolvable after a peel, in which case the peel is
+/// undone and the next one tried. When no line can be peeled the region left is handed to a
+/// weighted A* search with pattern databases capped at `MAX_REGION_STATES`, before any single
+/// cell is tried, and when all of that fails the whole board is left to one within
+/// `config.limits`.
+///
+/// Every placement that fails costs up to `MAX_PLACE_TIME`, so the time taken depends on how many
+/// peels the walls dead-end rather than on the size of the board: open boards up to 7x7 take well
+/// under a second in a release build, boards whose walls dead-end several peels a few seconds.
+///
+/// The answer is shortened by `optimise` before it is returned, unless the solve was cancelled.
+pub(crate) fn solve(puzzle: &Puzzle, config: &SolveConfig, budget: &mut Budget) -> SolveOutcome {
+    // Searches run for the reduction share its deadline rather than each starting a new clock
+    let limits = SolveLimits { time_limit: None, deadline: budget.deadline(), ..config.limits.clone() };
+    let exact = SolveConfig { heuristic: config.heuristic.clone(), limits: limits.clone(), pdb_dir: config.pdb_dir.clone(), ..SolveConfig::new(Algorithm::AStar) };
+    let fallback = SolveConfig { limits, pdb_dir: config.pdb_dir.clone(), ..SolveConfig::new(Algorithm::WeightedAStar) };
+
+    let mut reducer = Reducer::new(puzzle);
+    let solved = match reducer.reduce(&exact, &fallback, budget) {
+        Ok(false) => reducer.finish(&fallback),
+        solved => solved,
+    };
+
+    match solved {
+        // A cancelled solve hands back its answer at once instead of shortening it first
+        Ok(true) if budget.is_cancelled() => SolveOutcome::Cancelled(Some(reducer.moves)),
+        Ok(true) => match optimise(puzzle, &reducer.moves, DEFAULT_WINDOW) {
+            Ok(moves) => SolveOutcome::Solved(moves),
+            Err(_) => SolveOutcome::Solved(reducer.moves),
+        },
+        Ok(false) => SolveOutcome::Unsolvable,
+        Err(stop) => stop.into(),
+    }
+}
+
+impl Reducer {
+    fn new(puzzle: &Puzzle) -> Self {
+        let solved = puzzle.solved();
+        let mut reducer = Self {
+            width: puzzle.width as usize,
+            blank: puzzle.space_idx(),
+            goals: solved.goal_cells(),
+            blank_goal: solved.space_idx(),
+            fixed: puzzle.tiles.iter().map(|t| t.rune == Rune::WALL).collect(),
+            tiles: puzzle.tiles.clone(),
+            moves: vec![],
+        };
+
+        // Tiles walled off from the blank never move, and neither do tiles in dead ends the blank
+        // is not in: it could only leave again by putting them back. A solvable board has them
+        // home already.
+        let reached = reducer.reach(reducer.free(), reducer.blank);
+        for idx in 0..reducer.tiles.len() {
+            reducer.fixed[idx] |= reached >> idx & 1 == 0;
+        }
+        while let Some(dead_end) = cells(reducer.free()).find(|&idx| {
+            idx != reducer.blank && idx != reducer.blank_goal && reducer.degree(idx, reducer.free()) < 2
+        }) {
+            reducer.fixed[dead_end] = true;
+        }
+        reducer
+    }
+
+    /// The cell next to `idx` in direction `dir`, unless it is off the board
+    fn adjacent(&self, idx: usize, dir: Direction) -> Option<usize> {
+        dir.step(idx, self.width, self.tiles.len())
+    }
+
+    /// The cell the blank reaches by moving `dir`, unless it is off the board or fixed
+    fn neighbour(&self, idx: usize, dir: Direction) -> Option<usize> {
+        self.adjacent(idx, dir).filter(|&next| !self.fixed[next])
+    }
+
+    fn apply(&mut self, dir: Direction) {
+        let next = self.neighbour(self.blank, dir).expect("reduction only makes legal moves");
+        self.tiles.swap(self.blank, next);
+        self.blank = next;
+        self.moves.push(dir);
+    }
+
+    fn free(&self) -> Cells {
+        (0..self.tiles.len()).filter(|&idx| !self.fixed[idx]).fold(0, |set, idx| set | 1 << idx)
+    }
+
+    /// Peels lines until the board is small enough for `exact` to finish it, and tells whether
+    /// it did. A peel can leave the rest unsolvable, say a single loop whose tiles ended up in the
+    /// wrong order, so each one is undone again if the rest cannot be finished.
+    fn reduce(&mut self, exact: &SolveConfig, fallback: &SolveConfig, budget: &mut Budget) -> Result<bool, Stop> {
+        if self.free().count_ones() as usize <= FINAL_CELLS {
+            return self.finish(exact);
+        }
+
+        let (lines, singles): (Vec<Peel>, Vec<Peel>) = self.peels().into_iter().partition(|peel| peel.side > 0);
+        for peel in &lines {
+            if self.peel(peel, exact, fallback, budget)? {
+                return Ok(true);
+            }
+        }
+
+        // Single cells rarely help once no line does, the region search is tried first
+        let max_stored = fallback.limits.max_stored.map_or(MAX_REGION_STATES, |max| max.min(MAX_REGION_STATES));
+        let region = SolveConfig { heuristic: HeuristicKind::PatternDatabase, limits: SolveLimits { max_stored: Some(max_stored), ..fallback.limits.clone() }, ..fallback.clone() };
+        match self.finish(&region) {
+            Err(Stop::Limit(Limit::Stored)) | Ok(false) => {}
+            finished => return finished,
+        }
+
+        for peel in &singles {
+            if self.peel(peel, exact, fallback, budget)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Places `peel` and reduces the rest, undoing the peel again if that fails
+    fn peel(&mut self, peel: &Peel, exact: &SolveConfig, fallback: &SolveConfig, budget: &mut Budget) -> Result<bool, Stop> {
+        let (tiles, blank, moves) = (self.tiles.clone(), self.blank, self.moves.len());
+        if self.place_steps(&peel.steps, budget)? && self.remaining().is_solvable() && self.reduce(exact, fallback, budget)? {
+            return Ok(true);
+        }
+
+        peel.steps.iter().flatten().for_each(|&idx| self.fixed[idx] = false);
+        (self.tiles, self.blank) = (tiles, blank);
+        self.moves.truncate(moves);
+        Ok(false)
+    }
+
+    /// Searches the open cells that are left, adding the answer to the moves if there is one
+    fn finish(&mut self, config: &SolveConfig) -> Result<bool, Stop> {
+        let result = self.remaining().search(config).expect("the reduction only runs A* and weighted A* with admissible heuristics");
+        match result.outcome {
+            SolveOutcome::Solved(moves) => {
+                moves.iter().for_each(|&dir| self.apply(dir));
+                Ok(true)
+            }
+            SolveOutcome::Unsolvable | SolveOutcome::BudgetExhausted(Limit::Threshold, _) => Ok(false),
+            SolveOutcome::BudgetExhausted(limit, _) => Err(Stop::Limit(limit)),
+            SolveOutcome::Cancelled(_) => Err(Stop::Cancelled),
+        }
+    }
+
+    /// The top row and left column of the open region that can be peeled, the one that suits the
+    /// walls better first, then single cells far from the blank's goal. Peels leaving fewer walls
+    /// enclosed by open cells come first of all, the loop around such walls is slow to solve. Cells
+    /// a peel would leave with a single open neighbour join it after the cell next to them: their
+    /// tile could never change again, so they are placed along with the peel.
+    fn peels(&self) -> Vec<Peel> {
+        let free = self.free();
+        let Some((top, bottom, left, right)) = self.bounds(free) else { return vec![] };
+
+        let peel = |mut line: Vec<usize>, side: usize| {
+            let mut rest = line.iter().fold(free, |set, &idx| set & !(1 << idx));
+            while let Some(dead_end) = cells(rest).find(|&idx| idx != self.blank_goal && self.degree(idx, rest) < 2 && self.degree(idx, free) >= 2) {
+                rest &= !(1 << dead_end);
+                let after = line.iter().position(|&idx| self.degree(idx, 1 << dead_end) == 1).map_or(line.len(), |pos| pos + 1);
+                line.insert(after, dead_end);
+            }
+
+            Some(Peel { steps: self.steps(free, &line)?, holes: self.holes(rest), walls_left: self.walls_within(rest), side })
+        };
+
+        let goal = (self.blank_goal / self.width, self.blank_goal % self.width);
+        let mut singles: Vec<usize> = cells(free).collect();
+        singles.sort_by_key(|&idx| usize::MAX - (idx / self.width).abs_diff(goal.0) - (idx % self.width).abs_diff(goal.1));
+
+        let mut peels: Vec<Peel> = [
+            peel(cells(free).filter(|idx| idx / self.width == top).collect(), bottom - top + 1),
+            peel(cells(free).filter(|idx| idx % self.width == left).collect(), right - left + 1),
+        ].into_iter().chain(singles.into_iter().map(|idx| peel(vec![idx], 0))).flatten().collect();
+        // Singles stay in order of distance from the blank's goal among themselves
+        peels.sort_by_key(|peel| (peel.holes, peel.side == 0, if peel.side == 0 { 0 } else { peel.walls_left }, usize::MAX - peel.side));
+
+        let mut singles = 0;
+        peels.retain(|peel| peel.side > 0 || { singles += 1; singles <= MAX_SINGLE_PEELS });
+        peels
+    }
+
+    /// Splits `line` into the shortest steps that each leave `region` connected around the
+    /// blank's goal without a new cut cell, if no step needs more than `MAX_TRACKED` cells
+    fn steps(&self, mut region: Cells, line: &[usize]) -> Option<Vec<Vec<usize>>> {
+        let mut steps = vec![];
+        let mut start = 0;
+
+        for end in 1..=line.len() {
+            let rest = line[start..end].iter().fold(region, |set, &idx| set & !(1 << idx));
+            if rest >> self.blank_goal & 1 == 1 && self.reach(rest, self.blank_goal) == rest && self.cut_cells(rest) <= self.cut_cells(region) {
+                steps.push(line[start..end].to_vec());
+                (region, start) = (rest, end);
+            } else if end - start >= MAX_TRACKED {
+                return None;
+            }
+        }
+
+        (start == line.len()).then_some(steps)
+    }
+
+    /// Neighbours of `idx` in `region`
+    fn degree(&self, idx: usize, region: Cells) -> usize {
+        Direction::ALL.into_iter().filter(|&dir| self.adjacent(idx, dir).is_some_and(|next| region >> next & 1 == 1)).count()
+    }
+
+    /// Cells of `region` connected to `start`
+    fn reach(&self, region: Cells, start: usize) -> Cells {
+        let mut reached: Cells = 1 << start;
+        let mut queue = VecDeque::from([start]);
+
+        while let Some(idx) = queue.pop_front() {
+            for next in Direction::ALL.into_iter().filter_map(|dir| self.adjacent(idx, dir)) {
+                if region >> next & 1 == 1 && reached >> next & 1 == 0 {
+                    reached |= 1 << next;
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        reached
+    }
+
+    /// Cells whose loss would split `region`
+    fn cut_cells(&self, region: Cells) -> usize {
+        cells(region).filter(|&idx| {
+            let rest = region & !(1 << idx);
+            cells(rest).next().is_some_and(|start| self.reach(rest, start) != rest)
+        }).count()
+    }
+
+    /// Top and bottom rows, and left and right columns, of the box around `region`
+    fn bounds(&self, region: Cells) -> Option<(usize, usize, usize, usize)> {
+        let rows = cells(region).map(|idx| idx / self.width);
+        let columns = cells(region).map(|idx| idx % self.width);
+        Some((rows.clone().min()?, rows.max()?, columns.clone().min()?, columns.max()?))
+    }
+
+    /// Cells outside `region` that it closes a loop around, cut off from the edge of the board.
+    /// Cells touching at a corner count as connected, tiles cannot pass between them.
+    fn holes(&self, region: Cells) -> usize {
+        let (width, height) = (self.width as isize, (self.tiles.len() / self.width) as isize);
+        let mut seen = region;
+        let mut holes = 0;
+
+        for start in (0..self.tiles.len()).filter(|&idx| region >> idx & 1 == 0) {
+            if seen >> start & 1 == 1 {
+                continue;
+            }
+            seen |= 1 << start;
+            let mut queue = VecDeque::from([start]);
+            let mut enclosed = true;
+            let mut size = 0;
+
+            while let Some(idx) = queue.pop_front() {
+                size += 1;
+                let (row, col) = ((idx / self.width) as isize, (idx % self.width) as isize);
+                enclosed &= row > 0 && col > 0 && row + 1 < height && col + 1 < width;
+                for (dr, dc) in (-1..=1).flat_map(|dr| (-1..=1).map(move |dc| (dr, dc))) {
+                    let (r, c) = (row + dr, col + dc);
+                    if (0..height).contains(&r) && (0..width).contains(&c) {
+                        let next = (r * width + c) as usize;
+                        if seen >> next & 1 == 0 {
+                            seen |= 1 << next;
+                            queue.push_back(next);
+                        }
+                    }
+                }
+            }
+
+            holes += if enclosed { size } else { 0 };
+        }
+
+        holes
+    }
+
+    fn walls_within(&self, region: Cells) -> usize {
+        let Some((top, bottom, left, right)) = self.bounds(region) else { return 0 };
+        (top..=bottom)
+            .flat_map(|row| (left..=right).map(move |col| row * self.width + col))
+            .filter(|&idx| self.tiles[idx].rune == Rune::WALL)
+            .count()
+    }
+
+    /// Places and fixes every step of a peel in turn. A step that cannot be placed on its own,
+    /// because the tiles already fixed walled it in, is retried together with the step before.
+    /// A peel that cannot be finished is released again.
+    fn place_steps(&mut self, steps: &[Vec<usize>], budget: &mut Budget) -> Result<bool, Stop> {
+        for (i, step) in steps.iter().enumerate() {
+            if self.place(step, budget)? {
+                step.iter().for_each(|&idx| self.fixed[idx] = true);
+                continue;
+            }
+
+            if i > 0 && steps[i - 1].len() + step.len() <= MAX_TRACKED {
+                steps[i - 1].iter().for_each(|&idx| self.fixed[idx] = false);
+                let joined = [&steps[i - 1][..], step].concat();
+                if self.place(&joined, budget)? {
+                    joined.iter().for_each(|&idx| self.fixed[idx] = true);
+                    continue;
+                }
+            }
+
+            steps.iter().flatten().for_each(|&idx| self.fixed[idx] = false);
+            return Ok(false);
+        }
+
+        Ok(true)
+    }
+
+    /// Moves the tiles belonging on `group` home one more at a time, each search keeping the
+    /// tiles already home in place. A long group along a loop, whose tiles can only come home
+    /// together, is out of reach of a single search that tracks them all from the start.
+    fn place(&mut self, group: &[usize], budget: &mut Budget) -> Result<bool, Stop> {
+        for end in 1..=group.len() {
+            if !self.place_all(&group[..end], budget)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Moves the tiles belonging on `group` home, tracking only those tiles and the blank. The
+    /// search is weighted towards states whose tiles have the shortest walk home around walls
+    /// and fixed cells, so it finds a route quickly rather than the shortest one. Every other
+    /// tile may end up anywhere outside the fixed cells.
+    fn place_all(&mut self, group: &[usize], budget: &mut Budget) -> Result<bool, Stop> {
+        let encode = |blank: usize, positions: &[usize]| {
+            positions.iter().enumerate().fold(blank as u64, |key, (i, &pos)| key | (pos as u64) << (6 * (i + 1)))
+        };
+        let decode = |key: u64, positions: &mut [usize]| {
+            for (i, pos) in positions.iter_mut().enumerate() {
+                *pos = (key >> (6 * (i + 1)) & 63) as usize;
+            }
+            (key & 63) as usize
+        };
+
+        let mut positions: Vec<usize> = group.iter()
+            .map(|&goal| self.tiles.iter().position(|t| t.rune == Rune::VALUE && self.goals[t.rank() as usize] == goal).unwrap())
+            .collect();
+        if positions == group {
+            return Ok(true);
+        }
+
+        let walks: Vec<[u32; 64]> = group.iter().map(|&goal| self.walks(goal)).collect();
+        let blank_walks: Vec<[u32; 64]> = (0..self.tiles.len()).map(|idx| if self.fixed[idx] { [u32::MAX; 64] } else { self.walks(idx) }).collect();
+        // Each tile's walk home, and the blank's walk to the nearest tile not home yet
+        let estimate = |blank: usize, positions: &[usize]| {
+            let tiles = positions.iter().zip(&walks).map(|(&pos, walk)| walk[pos]).sum::<u32>();
+            let reach = positions.iter().zip(group).filter(|(pos, goal)| pos != goal).map(|(&pos, _)| blank_walks[blank][pos]).min().map_or(0, |walk| walk - 1);
+            tiles + reach
+        };
+
+        let start = encode(self.blank, &positions);
+        let mut parents: FxHashMap<u64, (u64, Direction, u32)> = FxHashMap::default();
+        let mut open = BinaryHeap::from([Reverse((PLACE_WEIGHT * estimate(self.blank, &positions), 0, start))]);
+        parents.insert(start, (start, Direction::UP, 0));
+        let started = Instant::now();
+
+        while let Some(Reverse((_, g, key))) = open.pop() {
+            budget.expand(parents.len())?;
+            if parents.len() > MAX_PLACE_STATES || started.elapsed() > MAX_PLACE_TIME {
+                return Ok(false);
+            }
+            if parents[&key].2 < g {
+                continue;
+            }
+            let blank = decode(key, &mut positions);
+
+            if positions == group {
+                let mut route = vec![];
+                let mut key = key;
+                while key != start {
+                    let (parent, dir, _) = parents[&key];
+                    route.push(dir);
+                    key = parent;
+                }
+                route.iter().rev().for_each(|&dir| self.apply(dir));
+                return Ok(true);
+            }
+
+            for dir in Direction::ALL {
+                let Some(next) = self.neighbour(blank, dir) else { continue };
+                let mut moved = positions.clone();
+                if let Some(pos) = moved.iter_mut().find(|pos| **pos == next) {
+                    *pos = blank;
+                }
+
+                let child = encode(next, &moved);
+                if parents.get(&child).is_some_and(|&(_, _, seen)| seen <= g + 1) {
+                    continue;
+                }
+                parents.insert(child, (key, dir, g + 1));
+                open.push(Reverse((g + 1 + PLACE_WEIGHT * estimate(next, &moved), g + 1, child)));
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Moves from every open cell to `goal` around walls and fixed cells
+    fn walks(&self, goal: usize) -> [u32; 64] {
+        let mut walks = [u32::MAX; 64];
+        walks[goal] = 0;
+        let mut queue = VecDeque::from([goal]);
+
+        while let Some(idx) = queue.pop_front() {
+            for next in Direction::ALL.into_iter().filter_map(|dir| self.neighbour(idx, dir)) {
+                if walks[next] == u32::MAX {
+                    walks[next] = walks[idx] + 1;
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        walks
+    }
+
+    /// The board as it stands, with every fixed cell turned into a wall. Tiles are labelled by
+    /// their goal cell, which keeps the labels valid whichever cells are fixed.
+    fn remaining(&self) -> Puzzle {
+        let tiles: String = self.tiles.iter().zip(&self.fixed)
+            .map(|(tile, &fixed)| match tile.rune {
+                _ if fixed => '=',
+                Rune::VALUE => LABELS.chars().nth(self.goals[tile.rank() as usize]).expect("a goal cell is on the board"),
+                _ => tile.raw,
+            })
+            .collect();
+        format!("{},{},{}", self.width, self.tiles.len() / self.width, tiles).parse().expect("a reduced board is still a valid board")
+    }
+}
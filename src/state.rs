@@ -0,0 +1,202 @@
+use std::hash::{Hash, Hasher};
+use std::sync::OnceLock;
+use crate::puzzle::{Direction, Puzzle};
+use crate::tile::Rune::{SPACE, WALL};
+use crate::tile::Tile;
+
+/// Boards are at most 7x7
+pub const MAX_CELLS: usize = 49;
+/// Every cell takes 6 bits, 10 to a word so no cell straddles two words
+const CELLS_PER_WORD: usize = 10;
+const WORDS: usize = MAX_CELLS.div_ceil(CELLS_PER_WORD);
+const CELL_MASK: u64 = 0b11_1111;
+
+/// Walls take code 0, value tiles their rank (1..=61) and the blank 62
+pub const WALL_CODE: u8 = 0;
+pub const BLANK_CODE: u8 = 62;
+
+pub fn tile_code(tile: &Tile) -> u8 {
+    match tile.rune {
+        WALL => WALL_CODE,
+        SPACE => BLANK_CODE,
+        _ => tile.rank() as u8,
+    }
+}
+
+fn code_tile(code: u8) -> Tile {
+    let raw = match code {
+        WALL_CODE => '=',
+        BLANK_CODE => '0',
+        1..=9 => (b'0' + code) as char,
+        10..=35 => (b'a' + code - 10) as char,
+        _ => (b'A' + code - 36) as char,
+    };
+    Tile::new(raw)
+}
+
+/// Random keys for every (cell, code) pair, the same on every run so hashes can be compared
+/// across searches
+fn zobrist_keys() -> &'static [[u64; 64]; MAX_CELLS] {
+    static KEYS: OnceLock<[[u64; 64]; MAX_CELLS]> = OnceLock::new();
+
+    KEYS.get_or_init(|| {
+        // splitmix64
+        let mut seed: u64 = 0x5D1D_E9A2_7C3B_41F0;
+        let mut next = || {
+            seed = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+            let mut z = seed;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+            z ^ (z >> 31)
+        };
+
+        let mut keys = [[0; 64]; MAX_CELLS];
+        keys.iter_mut().flatten().for_each(|key| *key = next());
+        keys
+    })
+}
+
+pub fn zobrist_key(cell: usize, code: u8) -> u64 {
+    zobrist_keys()[cell][code as usize]
+}
+
+/// Zobrist hash of a whole board, the XOR of the key of every cell's contents. Moving the blank
+/// only changes two cells, so `zobrist_move` updates it in constant time.
+pub fn zobrist_hash(tiles: &[Tile]) -> u64 {
+    tiles.iter().enumerate().fold(0, |hash, (cell, tile)| hash ^ zobrist_key(cell, tile_code(tile)))
+}
+
+/// Hash change when the blank at `blank` swaps with the tile coded `code` at `target`
+pub fn zobrist_move(blank: usize, target: usize, code: u8) -> u64 {
+    zobrist_key(blank, BLANK_CODE) ^ zobrist_key(blank, code) ^ zobrist_key(target, code) ^ zobrist_key(target, BLANK_CODE)
+}
+
+/// A board packed into 6-bit cells, 48 bytes with its hash instead of a `Vec<Tile>` on the
+/// heap. Shape is not stored, searches pass the width along.
+#[derive(Debug, Clone, Copy)]
+pub struct PackedState {
+    words: [u64; WORDS],
+    blank: u8,
+    hash: u64,
+}
+
+impl PartialEq for PackedState {
+    fn eq(&self, other: &Self) -> bool {
+        self.words == other.words
+    }
+}
+
+impl Eq for PackedState {}
+
+impl Hash for PackedState {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.hash);
+    }
+}
+
+impl PackedState {
+    pub fn from_puzzle(puzzle: &Puzzle) -> Self {
+        let mut state = Self { words: [0; WORDS], blank: 0, hash: zobrist_hash(&puzzle.tiles) };
+
+        for (cell, tile) in puzzle.tiles.iter().enumerate() {
+            state.set(cell, tile_code(tile));
+            if tile.rune == SPACE {
+                state.blank = cell as u8;
+            }
+        }
+
+        state
+    }
+
+    pub fn get(&self, cell: usize) -> u8 {
+        (self.words[cell / CELLS_PER_WORD] >> (cell % CELLS_PER_WORD * 6) & CELL_MASK) as u8
+    }
+
+    fn set(&mut self, cell: usize, code: u8) {
+        let shift = cell % CELLS_PER_WORD * 6;
+        let word = &mut self.words[cell / CELLS_PER_WORD];
+        *word = *word & !(CELL_MASK << shift) | (code as u64) << shift;
+    }
+
+    pub fn blank(&self) -> usize {
+        self.blank as usize
+    }
+
+    #[cfg(test)]
+    pub fn zobrist(&self) -> u64 {
+        self.hash
+    }
+
+    /// The state after moving the blank, or `None` if it would leave the board or hit a wall
+    pub fn apply(&self, dir: Direction, width: usize, cells: usize) -> Option<Self> {
+        let blank = self.blank();
+        let target = dir.step(blank, width, cells)?;
+
+        let code = self.get(target);
+        if code == WALL_CODE {
+            return None;
+        }
+
+        let mut next = *self;
+        next.set(blank, code);
+        next.set(target, BLANK_CODE);
+        next.blank = target as u8;
+        next.hash ^= zobrist_move(blank, target, code);
+        Some(next)
+    }
+
+    /// Overwrites the tiles of a board of the same shape, so heuristics written against
+    /// `Puzzle` can score the state without allocating
+    pub fn write_tiles(&self, tiles: &mut [Tile]) {
+        for (cell, tile) in tiles.iter_mut().enumerate() {
+            *tile = code_tile(self.get(cell));
+        }
+    }
+}
+
+/// Search nodes stored flat, each pointing back at its parent with the move that led to it,
+/// so a node costs a few bytes on top of its state instead of a copy of the move history.
+pub struct SearchTree {
+    nodes: Vec<Node>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Node {
+    pub state: PackedState,
+    pub g: u32,
+    parent: u32,
+    last: Option<Direction>,
+}
+
+impl SearchTree {
+    pub const ROOT: u32 = 0;
+
+    pub fn new(root: PackedState) -> Self {
+        Self { nodes: vec![Node { state: root, g: 0, parent: Self::ROOT, last: None }] }
+    }
+
+    pub fn push(&mut self, state: PackedState, parent: u32, dir: Direction) -> u32 {
+        let g = self.nodes[parent as usize].g + 1;
+        self.nodes.push(Node { state, g, parent, last: Some(dir) });
+        (self.nodes.len() - 1) as u32
+    }
+
+    pub fn get(&self, idx: u32) -> &Node {
+        &self.nodes[idx as usize]
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Moves from the root to the node
+    pub fn path(&self, mut idx: u32) -> Vec<Direction> {
+        let mut moves = vec![];
+        while let Some(dir) = self.nodes[idx as usize].last {
+            moves.push(dir);
+            idx = self.nodes[idx as usize].parent;
+        }
+        moves.reverse();
+        moves
+    }
+}
@@ -1,13 +1,34 @@
 #[cfg(test)]
 mod tests {
     use std::collections::{HashMap};
-    use crate::{Direction, Heuristic, Puzzle, Tile};
-    use crate::Direction::{DOWN, RIGHT, UP};
+    use std::collections::VecDeque;
+    use std::str::FromStr;
+    use rustc_hash::FxHashSet;
+    use crate::puzzle::{Algorithm, Direction, ParseError, ParseField, Puzzle, PuzzleError, SolveConfig};
+    use crate::heuristic::{BoardHeuristic, HeuristicKind, Memo};
+    use std::sync::Arc;
+    use crate::pattern_db::{Layout, PatternDatabase, PatternDbError};
+    use crate::distances::{self, CellDistances};
+    use crate::limits::{BatchSummary, Budget, CancelToken, Limit, SolveLimits, SolveOutcome, SolveResult, SolveStats};
+    use crate::walking_distance::WalkingDistance;
+    use crate::state::{PackedState, SearchTree};
+    use crate::puzzle::hash_tiles;
+    use crate::traits::puzzle::Heuristic;
+    use crate::puzzle::Direction::{DOWN, LEFT, RIGHT, UP};
     use crate::util::read_puzzles;
+    use crate::budget::{BudgetPlanner, MoveCounts};
+    use crate::puzzle_set::{PuzzleSet, PuzzleSetError};
+    use crate::cache::{solve_cached, CachedSolution, MemoryCache, RedisCache, SolutionCache, DEFAULT_REDIS_URL};
+    use crate::cli::{Cli, CliError, Command, DEFAULT_INPUT, DEFAULT_PUZZLE_COUNT, DEFAULT_THRESHOLD};
+    use crate::optimise::{cancel_inverses, optimise, OptimiseError, DEFAULT_WINDOW};
+    use crate::puzzle::{parse_moves, serialize_moves};
+    use crate::validate::{validate, ValidationError, ValidationReport};
+    use crate::answers::AnswersFile;
+    use crate::mock_api::{self, MockApi};
 
     #[test]
     fn can_generate_legal_set_of_moves() {
-        let puzzles = read_puzzles();
+        let puzzles = read_puzzles().unwrap();
 
         let test_cases: HashMap<usize, Vec<Direction>> = [
             (1144, vec![Direction::UP, Direction::DOWN, Direction::LEFT, Direction::RIGHT]),
@@ -37,7 +58,7 @@ mod tests {
     fn calculates_manhattan_distance_correctly() {
         let puzzle_str = "3,3,123456708";
 
-        let mut puzzle = Puzzle::from_str(puzzle_str);
+        let mut puzzle = Puzzle::from_str(puzzle_str).unwrap();
 
         let solved = puzzle.solved();
         for tile in &puzzle.tiles {
@@ -51,7 +72,7 @@ mod tests {
         }
 
         // Sum up the distances to get the heuristic
-        assert_eq!(puzzle.get_heuristic(&solved), 2);
+        assert_eq!(puzzle.get_heuristic(&solved), 2.);
 
         puzzle.move_space(Direction::RIGHT).unwrap();
 
@@ -61,7 +82,7 @@ mod tests {
     fn calculates_inverse_manhattan_distance_correctly() {
         let puzzle_str = "3,3,12346075=";
 
-        let puzzle = Puzzle::from_str(puzzle_str);
+        let _puzzle = Puzzle::from_str(puzzle_str).unwrap();
 
     }
 
@@ -74,7 +95,7 @@ mod tests {
         // 4 6 0
         // 7 5 =
 
-        let mut puzzle = Puzzle::from_str(puzzle_str);
+        let puzzle = Puzzle::from_str(puzzle_str).unwrap();
         let solved = puzzle.solved();
 
         // 0 should be 2 moves away from its target position
@@ -86,9 +107,7 @@ mod tests {
 
             if tile.raw == '0' {
                 assert_eq!(distance, 2);
-            } else if tile.raw == '6' {
-                assert_eq!(distance, 1);
-            } else if tile.raw == '5' {
+            } else if tile.raw == '6' || tile.raw == '5' {
                 assert_eq!(distance, 1);
             } else {
                 assert_eq!(distance, 0);
@@ -96,20 +115,18 @@ mod tests {
         }
 
         // Sum up the distances to get the heuristic
-        assert_eq!(puzzle.get_heuristic(&solved), 4);
+        assert_eq!(puzzle.get_heuristic(&solved), 4.);
 
     }
 
 
     #[test]
     fn move_space_swaps_correctly() {
-        let mut puzzles = read_puzzles();
+        let mut puzzles = read_puzzles().unwrap();
         let puzzle = puzzles.get_mut(1144).unwrap();
         let starting_idx = puzzle.space_idx();
         let target_idx = starting_idx + puzzle.width as usize;
 
-        let space_idx = puzzle.space_idx();
-
         // Perform the move
         puzzle.move_space(Direction::DOWN).expect("illegal move made");
 
@@ -118,9 +135,9 @@ mod tests {
         // cannot move with wall
         assert!(puzzle.move_space(Direction::RIGHT).is_err());
 
-        let puzzle_str = "4,3,12345678a0b=";
+        let puzzle_str = "4,3,1234567890a=";
 
-        let mut puzzle = Puzzle::from_str(puzzle_str);
+        let mut puzzle = Puzzle::from_str(puzzle_str).unwrap();
 
         let start_idx = puzzle.space_idx();
         let expected_idx = start_idx - puzzle.width as usize;
@@ -137,7 +154,7 @@ mod tests {
 
         let puzzle_str = "3,3,123456708";
 
-        let mut puzzle = Puzzle::from_str(puzzle_str);
+        let mut puzzle = Puzzle::from_str(puzzle_str).unwrap();
         let solved = puzzle.solved();
 
         puzzle.move_space(Direction::RIGHT).unwrap();
@@ -147,15 +164,15 @@ mod tests {
 
     #[test]
     fn can_solve_basic() {
-        let puzzle_str = "4,3,123406785aC=";
+        let puzzle_str = "4,3,1234067859a=";
 
-        let answer = vec![DOWN, RIGHT, RIGHT];
+        let answer = [DOWN, RIGHT, RIGHT];
 
         let answer_str: String = answer.iter().map(|d| format!("{}", d.to_char())).collect();
 
-        let mut puzzle = Puzzle::from_str(puzzle_str);
+        let mut puzzle = Puzzle::from_str(puzzle_str).unwrap();
 
-        puzzle.solve(false);
+        puzzle.search(&SolveConfig::new(Algorithm::AStar)).unwrap();
 
         assert_eq!(puzzle.moves_str(), answer_str);
 
@@ -165,11 +182,1154 @@ mod tests {
     fn can_solve() {
         let puzzle_str = "3,4,03a21648b579";
 
-        let mut puzzle = Puzzle::from_str(puzzle_str);
+        let mut puzzle = Puzzle::from_str(puzzle_str).unwrap();
         let solved = puzzle.solved();
 
-        puzzle.solve(false);
+        puzzle.search(&SolveConfig::new(Algorithm::AStar)).unwrap();
 
         assert!(puzzle.is_solved(&solved))
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn parses_cli_commands_and_flags() {
+        let args = |s: &str| s.split_whitespace().map(String::from).collect::<Vec<_>>();
+
+        let cli = Cli::parse(args("solve -i puzzles.txt --output out.txt -t 42.5 -j 4 --algorithm ida")).unwrap();
+        assert_eq!(cli.command, Command::Solve);
+        assert_eq!(cli.options.input, "puzzles.txt");
+        assert_eq!(cli.options.output, "out.txt");
+        assert_eq!(cli.options.heuristic_threshold, Some(42.5));
+        assert_eq!(cli.options.solve_config().heuristic_threshold, 42.5);
+        assert_eq!(cli.options.threads, Some(4));
+        assert_eq!(cli.options.algorithm, Algorithm::IdaStar);
+
+        let cli = Cli::parse(args("show 1144")).unwrap();
+        assert_eq!(cli.command, Command::Show { index: 1144 });
+        assert_eq!(cli.options.input, DEFAULT_INPUT);
+
+        assert_eq!(Cli::parse(args("fetch")).unwrap().command, Command::Fetch { count: DEFAULT_PUZZLE_COUNT });
+        assert_eq!(Cli::parse(args("fetch 20000")).unwrap().command, Command::Fetch { count: 20000 });
+        assert_eq!(Cli::parse(args("fetch 50")), Err(CliError::InvalidValue("count".into(), "50".into())));
+
+        assert_eq!(Cli::parse(args("")), Err(CliError::MissingCommand));
+        assert_eq!(Cli::parse(args("show")), Err(CliError::MissingValue("index".into())));
+        assert_eq!(Cli::parse(args("solve --threads 0")), Err(CliError::InvalidValue("--threads".into(), "0".into())));
+        assert_eq!(Cli::parse(args("solve --fast")), Err(CliError::UnknownFlag("--fast".into())));
+        assert_eq!(Cli::parse(args("explode")), Err(CliError::UnknownCommand("explode".into())));
+
+        let cli = Cli::parse(args("optimise --window 8")).unwrap();
+        assert_eq!(cli.command, Command::Optimise);
+        assert_eq!(cli.options.window, 8);
+        assert_eq!(Cli::parse(args("validate -o out.txt")).unwrap().command, Command::Validate);
+    }
+
+    fn scrambled(puzzle_str: &str, moves: &[Direction]) -> Puzzle {
+        let mut puzzle = Puzzle::from_str(puzzle_str).unwrap();
+        for &dir in moves {
+            puzzle.move_space(dir).unwrap();
+        }
+        Puzzle::from_str(&puzzle.serialized()).unwrap()
+    }
+
+    /// `steps` legal moves picked at random from `puzzle`, always the same ones for a seed
+    fn random_walk(puzzle: &Puzzle, steps: usize, mut seed: u64) -> Vec<Direction> {
+        let mut puzzle = puzzle.clone();
+        (0..steps).map(|_| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            let moves = puzzle.legal_moves(puzzle.space_idx());
+            let dir = moves[seed as usize % moves.len()];
+            puzzle.move_space(dir).unwrap();
+            dir
+        }).collect()
+    }
+
+    /// Every board one move on from `puzzle`, less the one that undoes its last move
+    fn successors(puzzle: &Puzzle) -> Vec<Puzzle> {
+        puzzle.successor_moves().into_iter().map(|dir| {
+            let mut next = puzzle.clone();
+            next.move_space(dir).unwrap();
+            next
+        }).collect()
+    }
+
+    fn optimal_solution_length(puzzle: &Puzzle) -> usize {
+        let solved = puzzle.solved();
+        let mut visited = FxHashSet::default();
+        let mut queue = VecDeque::from([(puzzle.clone(), 0)]);
+
+        while let Some((state, depth)) = queue.pop_front() {
+            if state.is_solved(&solved) {
+                return depth;
+            }
+            for successor in successors(&state) {
+                if visited.insert(successor.serialized()) {
+                    queue.push_back((successor, depth + 1));
+                }
+            }
+        }
+
+        panic!("puzzle has no solution")
+    }
+
+    #[test]
+    fn ida_star_finds_optimal_solutions() {
+        let puzzles = [
+            scrambled("3,3,123456780", &[UP, LEFT, UP, LEFT, DOWN, RIGHT, DOWN, LEFT, UP, RIGHT, UP, RIGHT]),
+            scrambled("4,3,1234567890a=", &[UP, LEFT, UP, RIGHT, RIGHT, DOWN, LEFT, DOWN, RIGHT, UP, RIGHT, UP]),
+            Puzzle::from_str("4,3,1234067859a=").unwrap(),
+        ];
+
+        for puzzle in puzzles {
+            let expected = optimal_solution_length(&puzzle);
+            let solved = puzzle.solved();
+
+            let mut ida = puzzle.clone();
+            let moves = ida.search(&SolveConfig::new(Algorithm::IdaStar)).unwrap().outcome.moves().unwrap();
+
+            assert!(ida.is_solved(&solved));
+            assert_eq!(moves.len(), expected, "{} solved in {}", puzzle.serialized(), ida.moves_str());
+        }
+
+        // The threshold caps the search bound
+        let mut puzzle = Puzzle::from_str("4,3,1234067859a=").unwrap();
+        let ida = |heuristic_threshold| SolveConfig { heuristic_threshold, ..SolveConfig::new(Algorithm::IdaStar) };
+        assert_eq!(puzzle.search(&ida(3.)).unwrap().outcome, SolveOutcome::BudgetExhausted(Limit::Threshold, None));
+        assert_eq!(puzzle.search(&ida(4.)).unwrap().outcome.moves().map(|m| m.len()), Some(3));
+    }
+
+    #[test]
+    fn bidirectional_search_meets_in_the_middle() {
+        let puzzles = [
+            scrambled("3,3,123456780", &[UP, LEFT, UP, LEFT, DOWN, RIGHT, DOWN, LEFT, UP, RIGHT, UP, RIGHT]),
+            scrambled("4,3,1234567890a=", &[UP, LEFT, UP, RIGHT, RIGHT, DOWN, LEFT, DOWN, RIGHT, UP, RIGHT, UP]),
+            Puzzle::from_str("4,3,1234067859a=").unwrap(),
+            Puzzle::from_str("3,3,123456780").unwrap(),
+        ];
+
+        for puzzle in puzzles {
+            let expected = optimal_solution_length(&puzzle);
+            let solved = puzzle.solved();
+
+            let mut bidirectional = puzzle.clone();
+            let moves = bidirectional.search(&SolveConfig::new(Algorithm::Bidirectional)).unwrap().outcome.moves().unwrap();
+
+            assert!(bidirectional.is_solved(&solved));
+            assert_eq!(moves.len(), expected, "{} solved in {}", puzzle.serialized(), bidirectional.moves_str());
+        }
+
+        // Solutions longer than the threshold are not returned
+        let mut puzzle = Puzzle::from_str("4,3,1234067859a=").unwrap();
+        let config = SolveConfig { heuristic_threshold: 2., ..SolveConfig::new(Algorithm::Bidirectional) };
+        assert_eq!(puzzle.search(&config).unwrap().outcome, SolveOutcome::BudgetExhausted(Limit::Threshold, None));
+    }
+
+    #[test]
+    fn detects_unsolvable_boards() {
+        let solvable = [
+            "3,3,123456708",
+            "3,3,12346075=",
+            "4,3,1234067859a=",
+            "3,4,03a21648b579",
+            "2,2,3120",
+            "4,1,1230",
+            "3,3,1=3=56780",
+        ];
+
+        for puzzle_str in solvable {
+            let puzzle = Puzzle::from_str(puzzle_str).unwrap();
+            assert!(puzzle.check_solvable().is_ok(), "{} should be solvable: {:?}", puzzle_str, puzzle.check_solvable());
+        }
+
+        let unsolvable = [
+            // Swapped pair on a plain board
+            ("3,3,123456870", "parity"),
+            ("4,3,12345678a90=", "parity"),
+            // '3' is boxed into the corner by walls
+            ("3,3,3=1=56780", "sealed off"),
+            // A wall column splits the board in two
+            ("3,3,1=24=35=0", "sealed off"),
+            // Tiles in a single corridor keep their order
+            ("4,1,0213", "dead-end corridor"),
+            // A dead end never lets its tile go, even at the end of a corridor
+            ("4,1,3120", "dead end away"),
+            ("3,2,210==3", "dead end away"),
+            // A loop with a pocket off each side: the pocket holds '4' where '3' belongs
+            ("3,3,21435==0=", "dead end away"),
+            // Once its pocket is set aside this is a ring, whose tiles can only rotate
+            ("4,3,23145=6=780=", "closed loop"),
+            // The blank's goal is in a different region
+            ("3,2,10=2=3", "walled off"),
+        ];
+
+        for (puzzle_str, reason) in unsolvable {
+            let mut puzzle = Puzzle::from_str(puzzle_str).unwrap();
+            match puzzle.check_solvable() {
+                Err(PuzzleError::UnsolvableBoard(msg)) => assert!(msg.contains(reason), "{}: {}", puzzle_str, msg),
+                other => panic!("{} should be unsolvable, got {:?}", puzzle_str, other),
+            }
+            assert!(!puzzle.is_solvable());
+            assert_eq!(puzzle.search(&SolveConfig::new(Algorithm::AStar)).unwrap().outcome, SolveOutcome::Unsolvable);
+        }
+
+        // Anything reachable from the solved board by legal moves must be accepted
+        for solved_str in ["4,4,123=456789ab=cd0", "5,3,12=345=6789abc0", "3,3,1234=5670", "5,1,12340", "3,3,12345==0=", "4,3,12345=6=780="] {
+            let mut puzzle = Puzzle::from_str(solved_str).unwrap();
+            for dir in random_walk(&puzzle, 500, 0x9E3779B97F4A7C15) {
+                puzzle.move_space(dir).unwrap();
+                assert!(puzzle.check_solvable().is_ok(), "{} rejected: {:?}", puzzle.serialized(), puzzle.check_solvable());
+            }
+        }
+    }
+
+    #[test]
+    fn budget_planner_respects_every_direction_limit() {
+        assert_eq!("5, 6,7,8".parse::<MoveCounts>(), Ok(MoveCounts::new(5, 6, 7, 8)));
+        assert!("5,6,7".parse::<MoveCounts>().is_err());
+
+        // Answering 0 with "UU" would starve 1, the planner has to switch it to "LR"
+        let mut planner = BudgetPlanner::new(MoveCounts::new(2, 2, 2, 2), 4);
+        planner.add_candidate(0, "UU").unwrap();
+        planner.add_candidate(0, "LR").unwrap();
+        planner.add_candidate(1, "UU").unwrap();
+        planner.add_candidate(2, "DD").unwrap();
+        planner.add_candidate(2, "DD").unwrap();
+
+        assert_eq!(planner.candidates(2).len(), 1);
+
+        let plan = planner.plan();
+        assert_eq!(plan.answers, vec!["LR", "UU", "DD", ""]);
+        assert_eq!(plan.answered(), 3);
+        assert_eq!(plan.answerable, 3);
+        assert_eq!(plan.totals, MoveCounts::new(2, 2, 1, 1));
+
+        // Over budget: the cheaper answers are kept
+        let mut planner = BudgetPlanner::new(MoveCounts::new(3, 0, 3, 3), 3);
+        planner.add_candidate(0, "UUU").unwrap();
+        planner.add_candidate(1, "UL").unwrap();
+        planner.add_candidate(2, "UR").unwrap();
+        // A corrupted answer is rejected rather than charged as free
+        assert!(matches!(planner.add_candidate(2, "U R"), Err(PuzzleError::UnknownMove(1, ' '))));
+        assert_eq!(MoveCounts::from_moves("UULR").unwrap(), MoveCounts::new(2, 0, 1, 1));
+
+        let plan = planner.plan();
+        assert_eq!(plan.answers, vec!["", "UL", "UR"]);
+        assert!(plan.totals.fits_within(&MoveCounts::new(3, 0, 3, 3)));
+        assert!(plan.to_string().contains("answering 2 of 3 puzzles"));
+    }
+
+    #[test]
+    fn parses_puzzle_set_header() {
+        let set = PuzzleSet::parse("72187 81749 72303 81778 3\n3,3,123456708\n3,3,1234\n4,3,1234067859a=\n").unwrap();
+
+        assert_eq!(set.limits, MoveCounts { left: 72187, right: 81749, up: 72303, down: 81778 });
+        assert_eq!(set.len(), 3);
+        assert_eq!(set.get(2).unwrap().serialized(), "4,3,1234067859a=");
+
+        // A malformed line keeps its slot instead of failing the whole set
+        assert!(set.get(1).is_none());
+        assert_eq!(set.errors().map(|(idx, _)| idx).collect::<Vec<_>>(), vec![1]);
+
+        assert!(matches!(PuzzleSet::parse(""), Err(PuzzleSetError::MalformedHeader(_))));
+        assert!(matches!(PuzzleSet::parse("1 2 3 4\n3,3,123456708"), Err(PuzzleSetError::MalformedHeader(_))));
+        assert!(matches!(PuzzleSet::parse("1 2 x 4 1\n3,3,123456708"), Err(PuzzleSetError::MalformedHeader(_))));
+        assert!(matches!(
+            PuzzleSet::parse("1 2 3 4 3\n3,3,123456708"),
+            Err(PuzzleSetError::CountMismatch { declared: 3, found: 1 })
+        ));
+        assert!(matches!(PuzzleSet::load("does/not/exist.txt"), Err(PuzzleSetError::MissingFile(_))));
+    }
+
+    #[test]
+    fn rejects_malformed_puzzle_lines() {
+        let puzzle = Puzzle::from_str("10,1,123456789a0").unwrap_err();
+        assert_eq!(puzzle.field, ParseField::Tiles);
+
+        let puzzle = Puzzle::from_str("11,1,123456789a0").unwrap();
+        assert_eq!((puzzle.width, puzzle.height), (11, 1));
+
+        let cases = [
+            ("", ParseField::Width, 0),
+            ("x,3,123456780", ParseField::Width, 0),
+            ("0,3,", ParseField::Width, 0),
+            ("3", ParseField::Height, 2),
+            ("3,,123456780", ParseField::Height, 2),
+            ("3,3", ParseField::Tiles, 4),
+            ("3,3,12345678", ParseField::Tiles, 4),
+            ("3,3,1234567800", ParseField::Tiles, 4),
+            ("3,3,12345678=", ParseField::Tiles, 4),
+            ("3,3,1234567?0", ParseField::Tiles, 11),
+            ("3,3,123456700", ParseField::Tiles, 12),
+            ("3,3,123453780", ParseField::Tiles, 9),
+            ("3,3,123456790", ParseField::Tiles, 4),
+        ];
+
+        for (puzzle_str, field, offset) in cases {
+            match Puzzle::from_str(puzzle_str) {
+                Err(ParseError { field: f, offset: o, message }) => {
+                    assert_eq!((f, o), (field, offset), "{:?}: {}", puzzle_str, message);
+                },
+                Ok(_) => panic!("{:?} should not parse", puzzle_str),
+            }
+        }
+
+        // Labels count the open cells or number every cell, on boards with walls as well
+        assert!(Puzzle::from_str("3,3,12=456780").is_ok());
+        assert!(Puzzle::from_str("3,3,12=345670").is_ok());
+        for puzzle_str in ["3,3,12=45678Z0", "3,3,12=34567a0", "3,3,12=356780", "3,3,1a=bcdef0", "3,3,11=345670"] {
+            assert_eq!(Puzzle::from_str(puzzle_str).map_err(|e| e.field), Err(ParseField::Tiles), "{}", puzzle_str);
+        }
+    }
+
+    /// Stores a long answer, a shorter one and then a longer one again, the shorter one must stay
+    fn keeps_the_shortest_answer(cache: &dyn SolutionCache, board: &str) {
+        assert_eq!(cache.get(board).unwrap(), None);
+        assert!(cache.put_if_shorter(board, &CachedSolution::new("DRRUDD", "astar")).unwrap());
+        assert!(cache.put_if_shorter(board, &CachedSolution::new("DRR", "ida")).unwrap());
+        assert!(!cache.put_if_shorter(board, &CachedSolution::new("DRRLR", "astar")).unwrap());
+
+        let cached = cache.get(board).unwrap().unwrap();
+        assert_eq!((cached.moves.as_str(), cached.length, cached.solver.as_str()), ("DRR", 3, "ida"));
+    }
+
+    #[test]
+    fn redis_cache_keeps_the_shortest_answer() {
+        // Only runs where the docker-compose Redis is up
+        let Ok(cache) = RedisCache::connect(DEFAULT_REDIS_URL) else {
+            eprintln!("skipping, no Redis at {}", DEFAULT_REDIS_URL);
+            return;
+        };
+        let board = format!("test-{}-{}", std::process::id(), chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default());
+        keeps_the_shortest_answer(&cache, &board);
+
+        let mut connection = redis::Client::open(DEFAULT_REDIS_URL).unwrap().get_connection().unwrap();
+        let _: () = redis::cmd("DEL").arg(format!("slidepuzzle:solution:{}", board)).query(&mut connection).unwrap();
+    }
+
+    #[test]
+    fn solution_cache_is_consulted_before_solving() {
+        let cache = MemoryCache::new();
+        let board = "4,3,1234067859a=";
+
+        keeps_the_shortest_answer(&cache, board);
+
+        // A planted answer is returned without running the solver
+        let mut puzzle = Puzzle::from_str(board).unwrap();
+        let result = solve_cached(&cache, &mut puzzle, &SolveConfig::new(Algorithm::AStar)).unwrap();
+        assert_eq!((result.outcome, result.stats), (SolveOutcome::Solved(vec![DOWN, RIGHT, RIGHT]), SolveStats::default()));
+        assert_eq!(puzzle.moves_str(), "");
+
+        // Misses are solved and stored under the serialized board
+        let mut puzzle = Puzzle::from_str("3,3,123456708").unwrap();
+        let result = solve_cached(&cache, &mut puzzle, &SolveConfig::new(Algorithm::IdaStar)).unwrap();
+        assert_eq!(result.outcome, SolveOutcome::Solved(vec![RIGHT]));
+        assert!(result.stats.expanded > 0);
+        let cached = cache.get("3,3,123456708").unwrap().unwrap();
+        assert_eq!((cached.moves.as_str(), cached.solver.as_str()), ("R", "ida/manhattan"));
+
+        // Boards without an answer report why and leave the cache alone
+        let mut puzzle = Puzzle::from_str("3,3,213456780").unwrap();
+        assert_eq!(solve_cached(&cache, &mut puzzle, &SolveConfig::new(Algorithm::AStar)).unwrap().outcome, SolveOutcome::Unsolvable);
+        assert_eq!(cache.get("3,3,213456780").unwrap(), None);
+
+        // Threads share the cache directly and the shortest answer wins
+        let moves = ["RLRLR", "RLR", "R", "RLRLRLR"];
+        std::thread::scope(|scope| {
+            for moves in moves {
+                scope.spawn(|| cache.put_if_shorter("3,3,123456708", &CachedSolution::new(moves, "astar")).unwrap());
+            }
+        });
+        assert_eq!(cache.get("3,3,123456708").unwrap().unwrap().moves, "R");
+    }
+
+    #[test]
+    fn heuristics_are_picked_by_name() {
+        let kind: HeuristicKind = "max(manhattan, sum(lc))".parse().unwrap();
+        assert_eq!(kind, HeuristicKind::Max(vec![HeuristicKind::Manhattan, HeuristicKind::Sum(vec![HeuristicKind::LinearConflict])]));
+        assert_eq!(kind.to_string(), "max(manhattan,sum(linear-conflict))");
+        assert!(kind.is_admissible());
+        assert!("bogus".parse::<HeuristicKind>().is_err());
+        assert!("max(manhattan,bogus)".parse::<HeuristicKind>().is_err());
+
+        assert!(!HeuristicKind::Legacy.is_admissible());
+        assert!(!HeuristicKind::Sum(vec![HeuristicKind::Manhattan, HeuristicKind::LinearConflict]).is_admissible());
+
+        // 2 and 1 swapped in the top row: 2 moves of manhattan distance and one conflict
+        let puzzle = Puzzle::from_str("3,3,213456780").unwrap();
+        let solved = puzzle.solved();
+        let estimate = |kind: &str| kind.parse::<HeuristicKind>().unwrap().prepare(&solved, None).estimate(&puzzle);
+        assert_eq!(estimate("manhattan"), 2);
+        assert_eq!(estimate("linear-conflict"), 4);
+        assert_eq!(estimate("max(manhattan,linear-conflict)"), 4);
+        assert_eq!(estimate("sum(manhattan,linear-conflict)"), 6);
+
+        // Every admissible heuristic stays at or below the optimal solution length
+        let puzzle = scrambled("4,3,1234567890a=", &[UP, LEFT, UP, RIGHT, RIGHT, DOWN, LEFT, DOWN, RIGHT, UP, RIGHT, UP]);
+        let optimal = optimal_solution_length(&puzzle) as u32;
+        for kind in [HeuristicKind::Manhattan, HeuristicKind::LinearConflict] {
+            assert!(kind.prepare(&puzzle.solved(), None).estimate(&puzzle) <= optimal, "{} overestimates", kind);
+        }
+
+        // Optimal searches refuse heuristics that may overestimate
+        let config = SolveConfig { heuristic: HeuristicKind::Legacy, ..SolveConfig::new(Algorithm::IdaStar) };
+        assert!(matches!(config.validate(), Err(PuzzleError::InadmissibleHeuristic(_))));
+        assert!(matches!(Puzzle::from_str("3,3,123456708").unwrap().search(&config), Err(PuzzleError::InadmissibleHeuristic(_))));
+
+        let cli = Cli::parse(["solve", "--heuristic", "linear-conflict", "-a", "ida"].map(String::from)).unwrap();
+        assert_eq!(cli.options.solve_config().heuristic, HeuristicKind::LinearConflict);
+        assert_eq!(cli.options.solve_config().name(), "ida/linear-conflict");
+    }
+
+    #[test]
+    fn counts_linear_conflicts_around_walls() {
+        let conflicts = |board: &str| {
+            let puzzle = Puzzle::from_str(board).unwrap();
+            puzzle.linear_conflicts(&puzzle.solved().goal_cells())
+        };
+
+        assert_eq!(conflicts("3,3,123456780"), 0);
+        assert_eq!(conflicts("3,3,213456780"), 1);
+        // A fully reversed row keeps one tile and moves the other two out
+        assert_eq!(conflicts("3,3,321456780"), 2);
+        // 4 and 1 swapped in the first column
+        assert_eq!(conflicts("3,3,423156780"), 1);
+        // Conflicts in a row and a column add up
+        assert_eq!(conflicts("3,3,213756480"), 2);
+        // Tiles that already sit in their goal line but are not adjacent still conflict
+        assert_eq!(conflicts("4,1,3210"), 2);
+        // Rows never wrap into the next one
+        assert_eq!(conflicts("3,3,124356780"), 0);
+
+        // 1 and 2 are on opposite sides of the wall, going around it is not a conflict
+        assert_eq!(conflicts("5,1,2=130"), 0);
+        // Within a segment the usual counting applies
+        assert_eq!(conflicts("5,1,=3120"), 1);
+        // Column split by a wall
+        assert_eq!(conflicts("2,4,42=31560"), 0);
+
+        // The legacy heuristic now includes the conflicts
+        let puzzle = Puzzle::from_str("3,3,213456780").unwrap();
+        assert_eq!(puzzle.get_heuristic(&puzzle.solved()), 4.);
+
+        // Linear conflict stays admissible and lets IDA* find the same optimal answers
+        let puzzles = [
+            scrambled("3,3,123456780", &[UP, LEFT, UP, LEFT, DOWN, RIGHT, DOWN, LEFT, UP, RIGHT, UP, RIGHT]),
+            scrambled("4,3,1234567890a=", &[UP, LEFT, UP, RIGHT, RIGHT, DOWN, LEFT, DOWN, RIGHT, UP, RIGHT, UP]),
+            Puzzle::from_str("4,3,1234067859a=").unwrap(),
+            Puzzle::from_str("3,3,321654780").unwrap(),
+        ];
+        let config = SolveConfig { heuristic: HeuristicKind::LinearConflict, ..SolveConfig::new(Algorithm::IdaStar) };
+
+        for mut puzzle in puzzles {
+            let optimal = optimal_solution_length(&puzzle);
+            let estimate = HeuristicKind::LinearConflict.prepare(&puzzle.solved(), None).estimate(&puzzle);
+            assert!(estimate as usize <= optimal, "{} estimated {} but solves in {}", puzzle.serialized(), estimate, optimal);
+            assert_eq!(puzzle.search(&config).unwrap().outcome.moves().map(|moves| moves.len()), Some(optimal));
+        }
+    }
+
+    #[test]
+    fn pattern_databases_are_admissible_and_persist() {
+        let puzzles = [
+            scrambled("3,3,123456780", &[UP, LEFT, UP, LEFT, DOWN, RIGHT, DOWN, LEFT, UP, RIGHT, UP, RIGHT]),
+            Puzzle::from_str("3,3,321654780").unwrap(),
+            Puzzle::from_str("3,3,867254301").unwrap(),
+        ];
+
+        // 6-2 split of the 8-puzzle, built with the default group size
+        let solved = puzzles[0].solved();
+        let db = Arc::new(PatternDatabase::build(&solved));
+        assert_eq!(db.group_sizes(), vec![6, 2]);
+        assert_eq!(PatternDatabase::planned_group_sizes(&Puzzle::from_str("4,4,123456789abcdef0").unwrap()), vec![6, 6, 3]);
+        assert_eq!(PatternDatabase::planned_group_sizes(&Puzzle::from_str("5,5,123456789abcdefghijklmno0").unwrap()), vec![5, 5, 5, 5, 4]);
+
+        let pdb = db.heuristic(&solved);
+        let manhattan = HeuristicKind::Manhattan.prepare(&solved, None);
+        let config = SolveConfig { heuristic: HeuristicKind::Manhattan, ..SolveConfig::new(Algorithm::IdaStar) };
+
+        assert_eq!(pdb.estimate(&solved), 0);
+        for puzzle in puzzles {
+            let optimal = puzzle.clone().search(&config).unwrap().outcome.moves().unwrap().len() as u32;
+            let estimate = pdb.estimate(&puzzle);
+            assert!(estimate >= manhattan.estimate(&puzzle), "{} is weaker than manhattan", puzzle.serialized());
+            assert!(estimate <= optimal, "{} estimated {} but solves in {}", puzzle.serialized(), estimate, optimal);
+        }
+
+        // Walls are part of the layout, tiles are matched by goal cell so labels do not matter
+        let walled = scrambled("4,3,1234567890a=", &[UP, LEFT, UP, RIGHT, RIGHT, DOWN, LEFT, DOWN, RIGHT, UP, RIGHT, UP]);
+        let (in_order, by_cell) = (Puzzle::from_str("3,3,1=24=5360").unwrap(), Puzzle::from_str("3,3,1=36=7480").unwrap());
+        assert_eq!(Layout::of(&in_order), Layout::of(&by_cell));
+        assert_ne!(Layout::of(&walled), Layout::of(&solved));
+        let small = Arc::new(PatternDatabase::build(&in_order.solved()));
+        assert_eq!(small.heuristic(&in_order.solved()).estimate(&in_order), small.heuristic(&by_cell.solved()).estimate(&by_cell));
+
+        let walled_solved = walled.solved();
+        let goals = |cells: &[usize]| cells.to_vec();
+        let db = Arc::new(PatternDatabase::build_with_groups(&walled_solved, vec![goals(&[0, 1, 2, 3]), goals(&[4, 5, 6, 7]), goals(&[8, 9])]));
+        let optimal = optimal_solution_length(&walled) as u32;
+        assert!(db.heuristic(&walled_solved).estimate(&walled) <= optimal);
+
+        // Saved databases load back unchanged and are shared from then on, broken files are rejected
+        let dir = std::env::temp_dir().join(format!("slidepuzzle-pdb-{}", std::process::id()));
+        let path = dir.join(db.layout().file_name());
+        db.save(&path).unwrap();
+        assert_eq!(&PatternDatabase::load(&path).unwrap(), db.as_ref());
+        assert_eq!(crate::pattern_db::shared(&walled_solved, Some(&dir)).group_sizes(), vec![4, 4, 2]);
+
+        // The small database for a layout seen once is kept rather than built again
+        let once = Puzzle::from_str("3,3,1234=6780").unwrap().solved();
+        let first = crate::pattern_db::shared(&once, None);
+        let second = crate::pattern_db::shared(&once, None);
+        assert!(Arc::ptr_eq(&first, &second) || Arc::ptr_eq(&second, &crate::pattern_db::shared(&once, None)));
+
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+        assert!(matches!(PatternDatabase::load(&path), Err(PatternDbError::Corrupt(_))));
+
+        // A damaged file is rebuilt and replaced
+        let rebuilt = PatternDatabase::load_or_build(&walled_solved, &dir).unwrap();
+        assert_eq!(PatternDatabase::load(&path).unwrap(), rebuilt);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // IDA* with the pattern databases still finds optimal answers
+        let mut puzzle = walled.clone();
+        let config = SolveConfig { heuristic: "max(pdb,linear-conflict)".parse().unwrap(), ..SolveConfig::new(Algorithm::IdaStar) };
+        assert_eq!(puzzle.search(&config).unwrap().outcome.moves().map(|moves| moves.len() as u32), Some(optimal));
+    }
+
+    #[test]
+    fn walking_distance_is_admissible_on_every_board() {
+        // The 15-puzzle table is well known to hold 24964 states, rows and columns share it
+        let fifteen = Puzzle::from_str("4,4,123456789abcdef0").unwrap();
+        assert_eq!(WalkingDistance::new(&fifteen, false).table_sizes(), (24964, 24964));
+
+        // Visit every reachable board and compare against the true distance
+        for (solved_str, walls) in [("4,2,12345670", false), ("2,4,12345670", false), ("3,3,1234=5670", false), ("3,3,1234=5670", true)] {
+            let solved = Puzzle::from_str(solved_str).unwrap();
+            let walking = WalkingDistance::new(&solved, walls);
+            let manhattan = HeuristicKind::Manhattan.prepare(&solved, None);
+
+            let mut visited = FxHashSet::from_iter([solved.serialized()]);
+            let mut queue = VecDeque::from([(solved.clone(), 0)]);
+            while let Some((state, depth)) = queue.pop_front() {
+                let estimate = walking.estimate(&state);
+                assert!(estimate <= depth, "{} estimated {} but solves in {}", state.serialized(), estimate, depth);
+                if !solved_str.contains('=') {
+                    assert!(estimate >= manhattan.estimate(&state), "{} is weaker than manhattan", state.serialized());
+                }
+
+                for successor in successors(&state) {
+                    if visited.insert(successor.serialized()) {
+                        queue.push_back((Puzzle::from_str(&successor.serialized()).unwrap(), depth + 1));
+                    }
+                }
+            }
+        }
+
+        // Walls block the crossing between the first two rows everywhere but the last column
+        let walled = Puzzle::from_str("3,3,==1234560").unwrap();
+        let solved = walled.solved();
+        let blind = WalkingDistance::new(&solved, false);
+        let aware = HeuristicKind::WalkingDistanceWalls.prepare(&solved, None);
+        let scrambled_walled = scrambled("3,3,==1234560", &[UP, LEFT, LEFT, DOWN, RIGHT, UP, RIGHT, UP]);
+        assert!(aware.estimate(&scrambled_walled) >= blind.estimate(&scrambled_walled));
+        assert!(aware.estimate(&scrambled_walled) as usize <= optimal_solution_length(&scrambled_walled));
+
+        // The hardest 8-puzzle takes 31 moves
+        let mut hardest = Puzzle::from_str("3,3,867254301").unwrap();
+        let walking = WalkingDistance::new(&hardest.solved(), false);
+        assert!(walking.estimate(&hardest) <= 31);
+        let config = SolveConfig { heuristic: HeuristicKind::WalkingDistance, ..SolveConfig::new(Algorithm::IdaStar) };
+        assert_eq!(hardest.search(&config).unwrap().outcome.moves().map(|moves| moves.len()), Some(31));
+    }
+
+    #[test]
+    fn packed_states_track_moves_and_hashes() {
+        // Every label fits, including the blank and walls on the largest board
+        let largest = Puzzle::from_str("7,7,123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJ=LM0").unwrap();
+        let packed = PackedState::from_puzzle(&largest);
+        let mut unpacked = largest.clone();
+        unpacked.tiles.iter_mut().for_each(|tile| *tile = crate::tile::Tile::new('1'));
+        packed.write_tiles(&mut unpacked.tiles);
+        assert_eq!(unpacked.serialized(), largest.serialized());
+        assert_eq!(packed.zobrist(), hash_tiles(&largest.tiles));
+
+        // Edges and walls are refused, and the incremental hash matches a fresh one
+        let width = largest.width as usize;
+        let cells = largest.tiles.len();
+        assert!(packed.apply(DOWN, width, cells).is_none());
+        assert!(packed.apply(RIGHT, width, cells).is_none());
+
+        let mut puzzle = largest.clone();
+        let mut state = packed;
+        let mut tree = SearchTree::new(state);
+        let mut node = SearchTree::ROOT;
+        for dir in random_walk(&largest, 300, 0x2545F4914F6CDD1D) {
+            for refused in Direction::ALL.into_iter().filter(|&dir| state.apply(dir, width, cells).is_none()) {
+                assert!(puzzle.clone().move_space(refused).is_err());
+            }
+
+            puzzle.move_space(dir).unwrap();
+            state = state.apply(dir, width, cells).unwrap();
+            node = tree.push(state, node, dir);
+
+            assert_eq!(state, PackedState::from_puzzle(&puzzle));
+            assert_eq!(state.zobrist(), hash_tiles(&puzzle.tiles));
+            assert_eq!(state.blank(), puzzle.space_idx());
+        }
+
+        // Parent pointers rebuild the same move list the board recorded
+        assert_eq!(tree.get(node).g as usize, puzzle.moves_str().len());
+        assert_eq!(tree.path(node).into_iter().map(|dir| dir.to_char()).collect::<String>(), puzzle.moves_str());
+
+        // Undoing a move restores the hash exactly
+        let moved = packed.apply(UP, width, cells).unwrap();
+        assert_ne!(moved.zobrist(), packed.zobrist());
+        assert_eq!(moved.apply(DOWN, width, cells).unwrap().zobrist(), packed.zobrist());
+
+        // Steps off any edge of a 4x3 board go nowhere, rows do not wrap
+        let steps = |idx: usize| Direction::ALL.map(|dir| dir.step(idx, 4, 12));
+        assert_eq!(steps(0), [None, Some(4), None, Some(1)]);
+        assert_eq!(steps(7), [Some(3), Some(11), Some(6), None]);
+        assert_eq!(steps(8), [Some(4), None, None, Some(9)]);
+    }
+
+    #[test]
+    fn heuristics_update_from_the_moved_tile() {
+        let kinds = |names: &str| names.split(' ').map(|name| name.parse::<HeuristicKind>().unwrap()).collect::<Vec<_>>();
+        let boards = [
+            ("3,3,123456780", kinds("legacy manhattan linear-conflict pdb walking-distance walking-distance-walls max(manhattan,pdb)")),
+            ("3,3,1234=5670", kinds("legacy manhattan linear-conflict pdb walking-distance walking-distance-walls sum(manhattan,wd)")),
+            ("4,4,12=456789ab=def0", kinds("legacy manhattan linear-conflict walking-distance walking-distance-walls")),
+            ("5,3,123456789abcde0", kinds("legacy manhattan linear-conflict walking-distance walking-distance-walls")),
+            ("7,7,123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJ=LM0", kinds("legacy manhattan linear-conflict")),
+        ];
+
+        for (board, kinds) in boards {
+            let solved = Puzzle::from_str(board).unwrap().solved();
+            let width = solved.width as usize;
+            let cells = solved.tiles.len();
+
+            for kind in kinds {
+                let heuristic = kind.prepare(&solved, None);
+                let mut puzzle = solved.clone();
+                let mut state = PackedState::from_puzzle(&puzzle);
+                let mut memo = Memo::default();
+                let mut estimate = heuristic.evaluate(&state, &mut memo);
+
+                for dir in random_walk(&solved, 400, 0x9E3779B97F4A7C15) {
+                    let next = state.apply(dir, width, cells).unwrap();
+                    puzzle.move_space(dir).unwrap();
+
+                    estimate = heuristic.update(&state, &next, estimate, &mut memo);
+                    state = next;
+
+                    let fresh = heuristic.evaluate(&state, &mut Memo::default());
+                    assert_eq!(estimate, fresh, "{} updated {} on {}", kind, estimate, puzzle.serialized());
+                    assert_eq!(heuristic.estimate(&puzzle), fresh);
+                    if kind == HeuristicKind::Legacy {
+                        assert_eq!(estimate as f32, puzzle.get_heuristic(&solved), "{}", puzzle.serialized());
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn distances_walk_around_walls() {
+        // A corridor bending round two walls: 0 -> 3 -> 6 -> 7 -> 8 -> 5 -> 2
+        let corridor = Puzzle::from_str("3,3,1=23=4560").unwrap();
+        let walks = distances::shared(&corridor);
+        assert_eq!(walks.get(0, 2), 6);
+        assert_eq!(walks.get(2, 0), 6);
+        assert_eq!(walks.get(3, 8), 3);
+        assert_eq!(walks.get(0, 1), distances::UNREACHABLE as u32);
+        assert_eq!(corridor.legacy_distance(0, 2), 4);
+
+        // One table per wall pattern, whatever the labels
+        let relabelled = Puzzle::from_str("3,3,1=34=6780").unwrap();
+        assert!(Arc::ptr_eq(&walks, &distances::shared(&relabelled)));
+        assert!(!Arc::ptr_eq(&walks, &distances::shared(&Puzzle::from_str("3,3,1234567=0").unwrap())));
+        assert_eq!(CellDistances::build(Layout::of(&corridor)), *walks);
+
+        // Every reachable board stays within its true distance
+        let solved = corridor.solved();
+        let manhattan = HeuristicKind::Manhattan.prepare(&solved, None);
+        let mut visited = FxHashSet::from_iter([solved.serialized()]);
+        let mut queue = VecDeque::from([(solved.clone(), 0)]);
+        let mut deepest = 0;
+        while let Some((state, depth)) = queue.pop_front() {
+            let estimate = manhattan.estimate(&state);
+            assert!(estimate <= depth, "{} estimated {} but solves in {}", state.serialized(), estimate, depth);
+            deepest = deepest.max(depth);
+
+            for successor in successors(&state) {
+                if visited.insert(successor.serialized()) {
+                    queue.push_back((Puzzle::from_str(&successor.serialized()).unwrap(), depth + 1));
+                }
+            }
+        }
+        assert!(deepest > 0);
+
+        // Boards score the sum of every tile's walk home
+        let far = scrambled("3,3,1=23=4560", &[LEFT, LEFT, UP, UP]);
+        assert_eq!(manhattan.estimate(&far), far.tiles.iter().enumerate()
+            .filter(|(_, tile)| tile.rune == crate::tile::Rune::VALUE)
+            .map(|(idx, tile)| walks.get(idx, solved.tiles.iter().position(|t| t.raw == tile.raw).unwrap()))
+            .sum::<u32>());
+    }
+
+    // Small boards and no files, so this also runs under Miri
+    #[test]
+    fn successors_never_undo_the_last_move() {
+        let centre = Puzzle::from_str("3,3,123405678").unwrap();
+        assert_eq!(format!("{:?}", centre.successor_moves()), "[UP, DOWN, LEFT, RIGHT]");
+
+        // The inverse of the previous move is left out, the rest keep their order
+        for dir in [UP, DOWN, LEFT, RIGHT] {
+            let mut successor = centre.clone();
+            successor.move_space(dir).unwrap();
+            let moves = successor.successor_moves();
+            assert!(!moves.into_iter().any(|next| next == dir.inverse()));
+            assert_eq!(moves.len(), 2);
+        }
+
+        // Walls and edges
+        let corridor = Puzzle::from_str("3,3,1=23=4560").unwrap();
+        assert_eq!(format!("{:?}", corridor.legal_moves(corridor.space_idx())), "[UP, LEFT]");
+        let mut walled = corridor.clone();
+        walled.move_space(LEFT).unwrap();
+        assert_eq!(format!("{:?}", walled.successor_moves()), "[LEFT]");
+
+        // Walking one board back and forth visits the same tree as cloning
+        fn count_cloned(puzzle: &Puzzle, depth: u32) -> usize {
+            if depth == 0 {
+                return 1;
+            }
+            1 + successors(puzzle).iter().map(|next| count_cloned(next, depth - 1)).sum::<usize>()
+        }
+        fn count_in_place(puzzle: &mut Puzzle, depth: u32) -> usize {
+            if depth == 0 {
+                return 1;
+            }
+            let mut count = 1;
+            for dir in puzzle.successor_moves() {
+                puzzle.move_space(dir).unwrap();
+                count += count_in_place(puzzle, depth - 1);
+                assert_eq!(puzzle.undo_move(), Some(dir));
+            }
+            count
+        }
+
+        for board in ["3,3,123405678", "3,3,1=23=4560", "4,2,1234=560"] {
+            let start = Puzzle::from_str(board).unwrap();
+            let mut puzzle = start.clone();
+            assert_eq!(count_in_place(&mut puzzle, 5), count_cloned(&start, 5));
+            assert_eq!(puzzle.serialized(), start.serialized());
+            assert_eq!(hash_tiles(&puzzle.tiles), hash_tiles(&start.tiles));
+            assert!(puzzle.moves_str().is_empty());
+            assert_eq!(puzzle.undo_move(), None);
+        }
+    }
+
+    #[test]
+    fn astar_with_a_consistent_heuristic_is_optimal() {
+        // Integer f values, so equal-cost nodes are told apart by h rather than by board contents
+        let boards = ["3,3,867254301", "3,3,321654780", "3,3,1234=5670", "4,3,1234567890a="];
+        for board in boards {
+            let puzzle = Puzzle::from_str(board).unwrap();
+            for heuristic in [HeuristicKind::Manhattan, HeuristicKind::LinearConflict] {
+                let astar = SolveConfig { heuristic: heuristic.clone(), ..SolveConfig::new(Algorithm::AStar) };
+                let ida = SolveConfig { heuristic, ..SolveConfig::new(Algorithm::IdaStar) };
+                let moves = puzzle.clone().search(&astar).unwrap().outcome.moves().unwrap();
+                assert_eq!(moves.len(), puzzle.clone().search(&ida).unwrap().outcome.moves().unwrap().len(), "{}", board);
+
+                let mut replayed = puzzle.clone();
+                moves.iter().for_each(|&dir| replayed.move_space(dir).unwrap());
+                assert!(replayed.is_solved(&puzzle.solved()));
+            }
+        }
+    }
+
+    #[test]
+    fn weighted_searches_stay_within_their_bound() {
+        let fifteen = Puzzle::from_str("4,4,123456789abcdef0").unwrap();
+        let fifteen = scrambled(&fifteen.serialized(), &random_walk(&fifteen, 60, 0x853C49E6748FEA9B));
+
+        for puzzle in [Puzzle::from_str("3,3,867254301").unwrap(), fifteen] {
+            let ida = SolveConfig { heuristic: HeuristicKind::LinearConflict, ..SolveConfig::new(Algorithm::IdaStar) };
+            let optimal = puzzle.clone().search(&ida).unwrap().outcome.moves().unwrap().len();
+
+            for weight in [1., 1.5, 3.] {
+                let config = SolveConfig { weight, ..SolveConfig::new(Algorithm::WeightedAStar) };
+                let moves = puzzle.clone().search(&config).unwrap().outcome.moves().unwrap();
+                assert!(moves.len() as f32 <= weight * optimal as f32, "weight {} took {} moves, optimal is {}", weight, moves.len(), optimal);
+            }
+
+            // Every answer is shorter than the last and within its bound, the final one optimal
+            let anytime = |config: &SolveConfig| puzzle.solve_anytime(config, &mut Budget::new(config.limits.clone())).0;
+            let solutions = anytime(&SolveConfig::new(Algorithm::AraStar));
+            assert!(!solutions.is_empty());
+            for pair in solutions.windows(2) {
+                assert!(pair[1].moves.len() < pair[0].moves.len());
+                assert!(pair[1].weight <= pair[0].weight);
+            }
+            for solution in &solutions {
+                assert!(solution.moves.len() as f32 <= solution.bound * optimal as f32 + 1e-3);
+                let mut replayed = puzzle.clone();
+                solution.moves.iter().for_each(|&dir| replayed.move_space(dir).unwrap());
+                assert!(replayed.is_solved(&puzzle.solved()));
+            }
+            let last = solutions.last().unwrap();
+            assert_eq!((last.moves.len(), last.bound), (optimal, 1.));
+            assert_eq!(puzzle.clone().search(&SolveConfig::new(Algorithm::AraStar)).unwrap().outcome.moves().map(|moves| moves.len()), Some(optimal));
+
+            // Out of budget after the first answer, which still carries a valid bound
+            let limited = SolveConfig { limits: SolveLimits { max_expanded: Some(1), ..SolveLimits::default() }, ..SolveConfig::new(Algorithm::AraStar) };
+            assert!(anytime(&limited).is_empty());
+            let first = SolveConfig { limits: SolveLimits { max_expanded: Some(solutions[0].moves.len() * 50), ..SolveLimits::default() }, ..SolveConfig::new(Algorithm::AraStar) };
+            if let Some(solution) = anytime(&first).pop() {
+                assert!(solution.moves.len() as f32 <= solution.bound * optimal as f32 + 1e-3);
+            }
+        }
+
+        // Weights below 1 would make the bound meaningless, and the bound needs an admissible heuristic
+        assert!(SolveConfig { weight: 0.5, ..SolveConfig::new(Algorithm::WeightedAStar) }.validate().is_err());
+        assert!(SolveConfig { heuristic: HeuristicKind::Legacy, ..SolveConfig::new(Algorithm::AraStar) }.validate().is_err());
+        assert_eq!(SolveConfig::new(Algorithm::WeightedAStar).name(), "wastar:2/linear-conflict");
+
+        let args = |s: &str| s.split_whitespace().map(String::from).collect::<Vec<_>>();
+        let cli = Cli::parse(args("solve -a ara -w 2.5 --time-limit 1.5 --node-limit 1000")).unwrap();
+        let config = cli.options.solve_config();
+        assert_eq!((config.algorithm, config.weight), (Algorithm::AraStar, 2.5));
+        assert_eq!(config.limits.time_limit, Some(std::time::Duration::from_millis(1500)));
+        assert_eq!(config.limits.max_expanded, Some(1000));
+        assert!(Cli::parse(args("solve --time-limit -1")).is_err());
+
+        // The default threshold only prunes the optimal searches, -t still applies to any of them
+        assert_eq!(config.heuristic_threshold, f32::MAX);
+        assert_eq!(Cli::parse(args("solve -a wastar")).unwrap().options.solve_config().heuristic_threshold, f32::MAX);
+        assert_eq!(Cli::parse(args("solve -a wastar -t 60")).unwrap().options.solve_config().heuristic_threshold, 60.);
+        assert_eq!(Cli::parse(args("solve -a ida")).unwrap().options.solve_config().heuristic_threshold, DEFAULT_THRESHOLD);
+    }
+
+    #[test]
+    fn every_solver_stops_at_its_limits() {
+        let hardest = Puzzle::from_str("3,3,867254301").unwrap();
+        let limited = |algorithm: Algorithm, limits: SolveLimits| {
+            let config = SolveConfig { limits, ..SolveConfig::new(algorithm) };
+            hardest.clone().search(&config).unwrap()
+        };
+
+        for algorithm in [Algorithm::AStar, Algorithm::IdaStar, Algorithm::Bidirectional, Algorithm::WeightedAStar, Algorithm::AraStar] {
+            let result = limited(algorithm, SolveLimits::default());
+            assert!(matches!(result.outcome, SolveOutcome::Solved(_)), "{} {}", algorithm, result.outcome);
+            assert!(result.stats.expanded > 0 && result.stats.peak_stored > 0);
+
+            let result = limited(algorithm, SolveLimits { max_expanded: Some(10), ..SolveLimits::default() });
+            assert_eq!(result.outcome, SolveOutcome::BudgetExhausted(Limit::Expanded, None), "{}", algorithm);
+            assert_eq!(result.stats.expanded, 11);
+
+            let result = limited(algorithm, SolveLimits { max_stored: Some(5), ..SolveLimits::default() });
+            assert_eq!(result.outcome, SolveOutcome::BudgetExhausted(Limit::Stored, None), "{}", algorithm);
+            assert!(result.stats.peak_stored > 5);
+
+            // A deadline that has already passed stops the first expansion
+            let result = limited(algorithm, SolveLimits { time_limit: Some(std::time::Duration::ZERO), ..SolveLimits::default() });
+            assert_eq!(result.outcome, SolveOutcome::BudgetExhausted(Limit::Time, None), "{}", algorithm);
+            assert_eq!(result.stats.expanded, 1);
+            let past = std::time::Instant::now() - std::time::Duration::from_secs(1);
+            let result = limited(algorithm, SolveLimits { deadline: Some(past), ..SolveLimits::default() });
+            assert_eq!(result.outcome, SolveOutcome::BudgetExhausted(Limit::Time, None), "{}", algorithm);
+
+            // A threshold that cuts every route short is reported rather than passed off as unsolvable
+            let config = SolveConfig { heuristic_threshold: 20., ..SolveConfig::new(algorithm) };
+            assert_eq!(hardest.clone().search(&config).unwrap().outcome, SolveOutcome::BudgetExhausted(Limit::Threshold, None), "{}", algorithm);
+
+            let unsolvable = Puzzle::from_str("3,3,213456780").unwrap();
+            assert_eq!(unsolvable.clone().search(&SolveConfig::new(algorithm)).unwrap().outcome, SolveOutcome::Unsolvable);
+        }
+
+        // ARA* hands back the answer it found before the limit cut its later passes short
+        let result = limited(Algorithm::AraStar, SolveLimits { max_expanded: Some(500), ..SolveLimits::default() });
+        let SolveOutcome::BudgetExhausted(Limit::Expanded, Some(moves)) = result.outcome else { panic!("ara {}", result.outcome) };
+        let mut replayed = hardest.clone();
+        moves.iter().for_each(|&dir| replayed.move_space(dir).unwrap());
+        assert!(replayed.is_solved(&hardest.solved()));
+
+        let inadmissible = SolveConfig { heuristic: HeuristicKind::Legacy, ..SolveConfig::new(Algorithm::IdaStar) };
+        assert!(hardest.clone().search(&inadmissible).is_err());
+    }
+
+    #[test]
+    fn batch_summary_counts_outcomes_by_kind() {
+        let result = |outcome: SolveOutcome, expanded: usize, peak_stored: usize| SolveResult {
+            outcome,
+            stats: SolveStats { expanded, peak_stored, elapsed: std::time::Duration::from_millis(expanded as u64) },
+        };
+        let results = [
+            Some(result(SolveOutcome::Solved(vec![UP]), 10, 4)),
+            Some(result(SolveOutcome::BudgetExhausted(Limit::Time, None), 20, 9)),
+            None,
+            Some(result(SolveOutcome::BudgetExhausted(Limit::Stored, None), 30, 2)),
+            Some(result(SolveOutcome::BudgetExhausted(Limit::Time, None), 5, 1)),
+            Some(result(SolveOutcome::Unsolvable, 0, 0)),
+            Some(result(SolveOutcome::Cancelled(None), 1, 1)),
+        ];
+
+        let mut summary = BatchSummary::default();
+        results.iter().for_each(|result| summary.add(result.as_ref()));
+        assert_eq!((summary.solved, summary.unsolvable, summary.cancelled, summary.skipped), (1, 1, 1, 1));
+        assert_eq!(summary.exhausted, vec![(Limit::Time, 2), (Limit::Stored, 1)]);
+        assert_eq!((summary.expanded, summary.peak_stored, summary.elapsed), (66, 9, std::time::Duration::from_millis(66)));
+        assert_eq!(
+            summary.to_string(),
+            "solved 1, unsolvable 1, stopped by the time limit 2, stopped by the state limit 1, cancelled 1, skipped 1\n\
+             expanded 66 states, at most 9 stored at once, 66.0ms searching"
+        );
+    }
+
+    #[test]
+    fn cancelled_solves_stop_and_report() {
+        // Takes IDA* with manhattan distance far longer than this test runs
+        let hard = Puzzle::from_str("4,4,def7bc95602148a3").unwrap();
+        assert!(hard.is_solvable());
+
+        for algorithm in [Algorithm::IdaStar, Algorithm::AStar, Algorithm::Bidirectional, Algorithm::AraStar] {
+            let cancel = CancelToken::new();
+            let config = SolveConfig {
+                heuristic: HeuristicKind::Manhattan,
+                limits: SolveLimits { cancel: Some(cancel.clone()), ..SolveLimits::default() },
+                ..SolveConfig::new(algorithm)
+            };
+
+            let canceller = std::thread::spawn(move || {
+                std::thread::sleep(std::time::Duration::from_millis(50));
+                cancel.cancel();
+            });
+            let result = hard.clone().search(&config).unwrap();
+            canceller.join().unwrap();
+
+            // ARA* keeps the best answer it had when cancelled, the rest have nothing to give
+            match (algorithm, result.outcome) {
+                (Algorithm::AraStar, SolveOutcome::Cancelled(Some(moves))) => {
+                    let mut replayed = hard.clone();
+                    moves.iter().for_each(|&dir| replayed.move_space(dir).unwrap());
+                    assert!(replayed.is_solved(&hard.solved()));
+                },
+                (_, outcome) => assert_eq!(outcome, SolveOutcome::Cancelled(None), "{}", algorithm),
+            }
+            assert!(result.stats.expanded > 0);
+            assert!(result.stats.elapsed < std::time::Duration::from_secs(5));
+        }
+
+        // A token cancelled up front stops the first expansion, clones share the flag
+        let cancel = CancelToken::new();
+        let config = SolveConfig { limits: SolveLimits { cancel: Some(cancel.clone()), ..SolveLimits::default() }, ..SolveConfig::new(Algorithm::IdaStar) };
+        assert_eq!(config.clone(), config);
+        cancel.clone().cancel();
+        let result = hard.clone().search(&config).unwrap();
+        assert_eq!((result.outcome, result.stats.expanded), (SolveOutcome::Cancelled(None), 1));
+        assert_ne!(CancelToken::new(), cancel);
+
+        let args = |s: &str| s.split_whitespace().map(String::from).collect::<Vec<_>>();
+        let cli = Cli::parse(args("solve --batch-time-limit 600 --state-limit 1000000")).unwrap();
+        assert_eq!(cli.options.batch_time_limit, Some(std::time::Duration::from_secs(600)));
+        assert_eq!(cli.options.solve_config().limits.max_stored, Some(1000000));
+    }
+
+    #[test]
+    fn reduction_solves_large_boards_with_walls() {
+        // Solved boards, labelled by cell, scrambled by a long random walk
+        let layouts: [(usize, usize, &[usize]); 6] = [
+            (5, 5, &[]),
+            (7, 7, &[]),
+            (6, 6, &[7]),
+            (7, 7, &[0, 9]),
+            (7, 7, &[3, 4, 5, 14, 20]),
+            (7, 6, &[0, 1, 7, 8, 15, 22]),
+        ];
+        let labels: Vec<char> = "123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ".chars().collect();
+
+        for (width, height, walls) in layouts {
+            let cells = width * height;
+            let tiles: String = (0..cells)
+                .map(|idx| if walls.contains(&idx) { '=' } else if idx == cells - 1 { '0' } else { labels[idx] })
+                .collect();
+
+            let solved_str = format!("{},{},{}", width, height, tiles);
+            let puzzle = scrambled(&solved_str, &random_walk(&Puzzle::from_str(&solved_str).unwrap(), 3000, 0x2545F4914F6CDD1D));
+
+            let limits = SolveLimits { time_limit: Some(std::time::Duration::from_secs(2)), ..SolveLimits::default() };
+            let result = puzzle.clone().search(&SolveConfig { limits, ..SolveConfig::new(Algorithm::Reduction) }).unwrap();
+            let moves = result.outcome.moves().unwrap_or_else(|| panic!("{} was not solved", puzzle.serialized()));
+            let mut replayed = puzzle.clone();
+            moves.iter().for_each(|&dir| replayed.move_space(dir).unwrap());
+            assert!(replayed.is_solved(&puzzle.solved()), "{} was not solved", puzzle.serialized());
+
+            // The answer never comes back to a layout it already passed through
+            let mut seen = FxHashSet::default();
+            let mut walked = puzzle.clone();
+            assert!(seen.insert(walked.serialized()));
+            for &dir in &moves {
+                walked.move_space(dir).unwrap();
+                assert!(seen.insert(walked.serialized()));
+            }
+        }
+
+        // Boards labelled in order rather than by cell reduce the same way
+        let in_order = scrambled("5,3,12=345=6789abc0", &[UP, UP, LEFT, DOWN, LEFT, DOWN, LEFT, LEFT, UP, UP, RIGHT]);
+        let moves = in_order.clone().search(&SolveConfig::new(Algorithm::Reduction)).unwrap().outcome.moves().unwrap();
+        let mut replayed = in_order.clone();
+        moves.iter().for_each(|&dir| replayed.move_space(dir).unwrap());
+        assert!(replayed.is_solved(&in_order.solved()));
+
+        // Wall layouts whose peels dead-end, these used to run past any time limit. They take a few
+        // seconds in a release build, about four times that in a debug one.
+        let time_limit = std::time::Duration::from_secs(if cfg!(debug_assertions) { 20 } else { 5 });
+        for board in ["4,4,9a718=5e0c=4f2d3", "5,4,1b8734g960cf==jha5i2", "3,7,h362=9f=gejc0dib1ak74"] {
+            let puzzle = Puzzle::from_str(board).unwrap();
+            let limits = SolveLimits { time_limit: Some(time_limit), ..SolveLimits::default() };
+            let result = puzzle.clone().search(&SolveConfig { limits, ..SolveConfig::new(Algorithm::Reduction) }).unwrap();
+            let moves = result.outcome.moves().unwrap_or_else(|| panic!("{} was not solved", board));
+            let mut replayed = puzzle.clone();
+            moves.iter().for_each(|&dir| replayed.move_space(dir).unwrap());
+            assert!(replayed.is_solved(&puzzle.solved()), "{} was not solved", board);
+        }
+
+        let unsolvable = Puzzle::from_str("3,3,213456780").unwrap();
+        assert_eq!(unsolvable.clone().search(&SolveConfig::new(Algorithm::Reduction)).unwrap().outcome, SolveOutcome::Unsolvable);
+    }
+
+    #[test]
+    fn optimise_shortens_answers_to_the_same_board() {
+        assert_eq!(cancel_inverses(&[UP, LEFT, RIGHT, DOWN, DOWN]), vec![DOWN]);
+        assert_eq!(serialize_moves(&parse_moves("ULDR").unwrap()), "ULDR");
+        assert!(matches!(parse_moves("UX"), Err(PuzzleError::UnknownMove(1, 'X'))));
+
+        // Detours that come back to an earlier board are cut, leaving the optimal answer
+        let puzzle = scrambled("3,3,123456780", &[UP, LEFT, UP, LEFT, DOWN, RIGHT, DOWN, LEFT, UP]);
+        let optimal = puzzle.clone().search(&SolveConfig::new(Algorithm::IdaStar)).unwrap().outcome.moves().unwrap();
+        let mut padded = [UP, RIGHT, DOWN, LEFT].repeat(3);
+        padded.extend_from_slice(&optimal[..3]);
+        padded.extend_from_slice(&[LEFT, RIGHT]);
+        padded.extend_from_slice(&optimal[3..]);
+        let shortened = optimise(&puzzle, &padded, DEFAULT_WINDOW).unwrap();
+        assert_eq!(shortened.len(), optimal.len());
+        let mut replayed = puzzle.clone();
+        shortened.iter().for_each(|&dir| replayed.move_space(dir).unwrap());
+        assert!(replayed.is_solved(&puzzle.solved()));
+
+        // Twice round a 2x2 block never repeats a board, once round the other way gets there too
+        let solved = Puzzle::from_str("3,3,123456780").unwrap();
+        let around = [UP, LEFT, DOWN, RIGHT].repeat(2);
+        let shortened = optimise(&solved, &around, DEFAULT_WINDOW).unwrap();
+        assert_eq!(shortened.len(), 4);
+        assert_eq!(scrambled("3,3,123456780", &shortened), scrambled("3,3,123456780", &around));
+
+        assert!(matches!(optimise(&solved, &[DOWN], DEFAULT_WINDOW), Err(OptimiseError::IllegalMove(0, _))));
+        assert!(matches!(optimise(&solved, &[UP, RIGHT], DEFAULT_WINDOW), Err(OptimiseError::IllegalMove(1, _))));
+    }
+
+    #[test]
+    fn validate_replays_answers_on_their_board() {
+        let puzzle = Puzzle::from_str("3,3,123456708").unwrap();
+
+        let report = validate(&puzzle, "RLR").unwrap();
+        assert!(report.is_valid());
+        assert_eq!(report, ValidationReport { illegal_move: None, solved: true, counts: MoveCounts::new(0, 0, 1, 2) });
+
+        // The replay stops at the first illegal move, the counts still cover the whole answer
+        let report = validate(&puzzle, "RRUD").unwrap();
+        assert_eq!(report.illegal_move, Some(1));
+        assert!(!report.solved);
+        assert_eq!(report.counts, MoveCounts::new(1, 1, 0, 2));
+
+        let report = validate(&puzzle, "UD").unwrap();
+        assert_eq!((report.illegal_move, report.solved), (None, false));
+        assert!(!report.is_valid());
+
+        let walls = Puzzle::from_str("3,3,12=450786").unwrap();
+        assert_eq!(validate(&walls, "U").unwrap().illegal_move, Some(0));
+        assert_eq!(validate(&puzzle, "RX"), Err(ValidationError::UnknownMove(1, 'X')));
+    }
+
+    #[test]
+    fn answers_file_round_trips_and_merges_shorter_answers() {
+        let answers = AnswersFile::from_answers(vec!["R".into(), String::new(), "UL".into(), String::new()]);
+        assert_eq!(answers.to_string(), "R\n\nUL\n\n");
+        assert_eq!(AnswersFile::parse(&answers.to_string()), answers);
+        assert_eq!(AnswersFile::parse("R\r\nD"), AnswersFile::from_answers(vec!["R".into(), "D".into()]));
+
+        // A set without a single answer still has a line per puzzle
+        let unanswered = AnswersFile::from_answers(vec![String::new(); 3]);
+        assert_eq!(unanswered.to_string(), "\n\n\n");
+        assert_eq!(AnswersFile::parse(&unanswered.to_string()), unanswered);
+        assert_eq!(AnswersFile::parse(""), AnswersFile::default());
+        assert_eq!((answers.answered(), answers.answers[2].as_str()), (2, "UL"));
+
+        let path = std::env::temp_dir().join(format!("slidepuzzle-answers-{}.txt", std::process::id()));
+        let path = path.to_str().unwrap();
+        answers.write(path).unwrap();
+        assert_eq!(AnswersFile::load(path).unwrap(), answers);
+        assert!(!std::path::Path::new(&format!("{}.tmp", path)).exists());
+        std::fs::remove_file(path).unwrap();
+
+        // Shorter and new answers that solve their puzzle are taken, anything else is ignored
+        let puzzles = PuzzleSet::parse("10 10 10 10 4\n3,3,123456708\n3,3,123450786\n3,3,123456078\n3,3,123456708\n").unwrap();
+        let mut answers = AnswersFile::from_answers(vec!["RLR".into(), "D".into(), String::new()]);
+        let previous = AnswersFile::from_answers(vec!["R".into(), "DUD".into(), "U".into(), "R".into()]);
+        assert_eq!(answers.merge(&previous, &puzzles), 2);
+        assert_eq!(answers.answers, vec!["R", "D", "", "R"]);
+    }
+
+    #[test]
+    fn mock_api_generates_and_scores_puzzle_sets() {
+        let questions = mock_api::generate(50, 11);
+        assert_eq!(questions, mock_api::generate(50, 11));
+        let puzzles = PuzzleSet::parse(&questions).unwrap();
+        assert_eq!((puzzles.len(), puzzles.limits), (50, MoveCounts::new(350, 350, 350, 350)));
+        assert_eq!(puzzles.errors().count(), 0);
+        assert!(puzzles.puzzles.iter().flatten().all(|puzzle| puzzle.is_solvable() && !puzzle.is_solved(&puzzle.solved())));
+
+        let first = puzzles.get(0).unwrap().clone().search(&SolveConfig::new(Algorithm::AStar)).unwrap().outcome.moves().unwrap();
+        let response = mock_api::score(&questions, &serialize_moves(&first)).unwrap();
+        assert_eq!(response.score, 2.);
+        assert_eq!(response.limit_up, 350);
+        assert_eq!(MoveCounts::new(response.count_up, response.count_down, response.count_left, response.count_right), MoveCounts::from_directions(&first));
+
+        // Going over any limit scores nothing
+        let tight = questions.replacen("350 350 350 350", "0 0 0 0", 1);
+        assert_eq!(mock_api::score(&tight, &serialize_moves(&first)).unwrap().score, 0.);
+        assert!(mock_api::score(&questions, &"\n".repeat(51)).is_err());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn fetch_solve_and_submit_against_the_mock_api() {
+        let api = MockApi::start(3).await.unwrap();
+        let dir = std::env::temp_dir().join(format!("slidepuzzle-mock-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (input, output) = (dir.join("slidepuzzle.txt"), dir.join("answers.txt"));
+        let cli = |command: &str| {
+            let args = format!("{} -i {} -o {} --api-url {} --no-cache", command, input.display(), output.display(), api.url());
+            Cli::parse(args.split_whitespace().map(String::from)).unwrap()
+        };
+
+        crate::run(cli("fetch")).await.unwrap();
+        assert_eq!(PuzzleSet::load(input.to_str().unwrap()).unwrap().len(), DEFAULT_PUZZLE_COUNT as usize);
+        crate::run(cli("solve")).await.unwrap();
+        crate::run(cli("validate")).await.unwrap();
+        crate::run(cli("submit")).await.unwrap();
+
+        let submissions = api.submissions();
+        assert_eq!(submissions.len(), 1);
+        assert_eq!(submissions[0].score, 100.);
+        assert!(submissions[0].count_up <= submissions[0].limit_up);
+
+        // The client reports errors from the API rather than failing to parse them
+        let missing = crate::api::submit_puzzle(&reqwest::Client::new(), &format!("{}/nowhere", api.url()), input.to_str().unwrap(), output.to_str().unwrap()).await;
+        assert!(missing.is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -22,11 +22,6 @@ pub struct Tile {
     pub solved_idx: Option<usize>
 }
 
-pub fn serialize_tiles(tiles: &Vec<Tile>) -> String {
-    tiles.iter().map(|t| t.raw).collect()
-}
-
-
 impl Tile {
     pub fn new(char: char) -> Self {
 
@@ -37,10 +32,6 @@ impl Tile {
         }
     }
 
-    pub fn raw(&self) -> char {
-        self.raw
-    }
-
     pub fn rank(&self) -> i32 {
         match self.raw {
             '1'..='9' => self.raw as i32 - '0' as i32,
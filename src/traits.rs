@@ -8,6 +8,7 @@ pub(crate) mod puzzle {
     }
 
     impl DebugPrintable for Puzzle {
+        #[allow(clippy::print_literal)]
         fn debug_print(&self, manhattan_distance: bool) {
             let solved = self.solved();
             print!("┌");
@@ -74,13 +75,14 @@ pub(crate) mod puzzle {
         fn get_heuristic(&self, solved_puzzle: &Puzzle) -> f32 {
             let mut heuristic: f32 = 0.;
 
-            for (idx, tile) in self.tiles.iter().enumerate() {
+            for tile in self.tiles.iter() {
                 heuristic+=self.manhattan_distance(tile, solved_puzzle) as f32;
-                // heuristic+=2 * self.linear_conflicts(idx)
             }
+            heuristic+=2. * self.linear_conflicts(&solved_puzzle.goal_cells()) as f32;
 
             heuristic
         }
+
     }
 }
 
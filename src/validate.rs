@@ -0,0 +1,64 @@
+use std::fmt;
+use crate::budget::MoveCounts;
+use crate::puzzle::{parse_moves, Puzzle, PuzzleError};
+
+/// What replaying an answer on its board showed
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationReport {
+    /// Index of the first move that could not be made, the replay stops there
+    pub illegal_move: Option<usize>,
+    /// Whether the board was solved once the moves were made
+    pub solved: bool,
+    /// Moves in each direction across the whole answer, as the contest counts them
+    pub counts: MoveCounts,
+}
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.illegal_move.is_none() && self.solved
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.illegal_move {
+            Some(idx) => write!(f, "move {} is illegal", idx + 1),
+            None if !self.solved => write!(f, "board is not solved"),
+            None => write!(f, "solved"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ValidationError {
+    /// A character other than U, D, L or R at this index
+    UnknownMove(usize, char),
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationError::UnknownMove(idx, char) => write!(f, "Unknown move {:?} at {}", char, idx + 1),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// Replays `moves`, written as in the answers file, on a copy of `puzzle` and reports whether
+/// they solve it
+pub fn validate(puzzle: &Puzzle, moves: &str) -> Result<ValidationReport, ValidationError> {
+    let moves = parse_moves(moves).map_err(|e| match e {
+        PuzzleError::UnknownMove(idx, char) => ValidationError::UnknownMove(idx, char),
+        e => unreachable!("parse_moves only rejects unknown moves: {}", e),
+    })?;
+
+    let mut board = puzzle.clone();
+    let illegal_move = moves.iter().position(|&dir| board.move_space(dir).is_err());
+
+    Ok(ValidationReport {
+        illegal_move,
+        solved: illegal_move.is_none() && board.is_solved(&puzzle.solved()),
+        counts: MoveCounts::from_directions(&moves),
+    })
+}
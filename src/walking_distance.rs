@@ -0,0 +1,294 @@
+use std::collections::hash_map::Entry;
+use std::sync::{Arc, Mutex, OnceLock};
+use rustc_hash::FxHashMap;
+use crate::heuristic::{moved_tile, BoardHeuristic, Memo};
+use crate::puzzle::Puzzle;
+use crate::state::{PackedState, BLANK_CODE, WALL_CODE};
+use crate::tile::Rune::WALL;
+
+/// Boards are at most 7x7
+const MAX_LINES: usize = 7;
+/// Tables stop growing after the breadth-first layer that takes them past this many states.
+/// Boards of 6x6 and up have far more, their missing states fall back to a lower bound.
+const MAX_STATES: usize = 1 << 22;
+
+/// Walking distance splits the board into lines (rows or columns) and only tracks how many
+/// tiles in each line belong to each goal line. A move between lines takes the blank to the
+/// next line and brings one tile the other way, so a breadth-first search over these count
+/// matrices gives the fewest vertical (or horizontal) moves needed. It is never less than the
+/// manhattan distance along that axis, and it also sees tiles blocking each other in a line.
+pub struct WalkingDistance {
+    rows: Axis,
+    columns: Axis,
+    goals: [usize; 64],
+    walls: bool,
+}
+
+/// One of the two directions, with the table shared between every board of the same shape
+struct Axis {
+    table: Arc<Table>,
+    /// Cell to line
+    line: Vec<u8>,
+}
+
+/// Everything a table depends on. Without walls this is only the board shape, so square
+/// boards share one table between rows and columns.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct TableKey {
+    /// Cells in each line that take part, the blank included
+    capacity: Vec<u8>,
+    blank: u8,
+    /// Bit `i` is set when a tile can move between lines `i` and `i + 1`
+    gates: u8,
+}
+
+struct Table {
+    key: TableKey,
+    distances: FxHashMap<u128, u8>,
+    /// Every state this close to the goal is in `distances`, `None` once the table is complete
+    horizon: Option<u8>,
+}
+
+type Counts = [[u8; MAX_LINES]; MAX_LINES];
+
+type SharedTable = Arc<OnceLock<Arc<Table>>>;
+
+static TABLES: Mutex<Option<FxHashMap<TableKey, SharedTable>>> = Mutex::new(None);
+
+impl WalkingDistance {
+    /// With `walls` set, wall cells take no part and tiles can only cross between lines where
+    /// some column (or row) is open on both sides. Without it walls count as tiles that are
+    /// already home, so the tables only depend on the board shape.
+    pub fn new(solved_puzzle: &Puzzle, walls: bool) -> Self {
+        let width = solved_puzzle.width as usize;
+        let height = solved_puzzle.height as usize;
+
+        let rows = Axis::new(solved_puzzle, walls, (0..width * height).map(|idx| (idx / width) as u8).collect(), height, width);
+        let columns = Axis::new(solved_puzzle, walls, (0..width * height).map(|idx| (idx % width) as u8).collect(), width, 1);
+
+        Self { rows, columns, goals: solved_puzzle.goal_cells(), walls }
+    }
+
+    /// Number of states in the row and column tables
+    #[cfg(test)]
+    pub fn table_sizes(&self) -> (usize, usize) {
+        (self.rows.table.distances.len(), self.columns.table.distances.len())
+    }
+
+    /// Goal cell of the tile coded `code` sitting at `idx`, `None` when it takes no part
+    fn goal(&self, idx: usize, code: u8) -> Option<usize> {
+        match code {
+            BLANK_CODE => None,
+            WALL_CODE => (!self.walls).then_some(idx),
+            _ => Some(self.goals[code as usize]),
+        }
+    }
+
+    fn axis_key(&self, axis: &Axis, state: &PackedState) -> u128 {
+        let mut counts: Counts = [[0; MAX_LINES]; MAX_LINES];
+
+        for (idx, &line) in axis.line.iter().enumerate() {
+            if let Some(goal) = self.goal(idx, state.get(idx)) {
+                counts[line as usize][axis.line[goal] as usize] += 1;
+            }
+        }
+
+        axis.table.encode(&counts, axis.line[state.blank()])
+    }
+}
+
+impl BoardHeuristic for WalkingDistance {
+    fn evaluate(&self, state: &PackedState, memo: &mut Memo) -> u32 {
+        *memo = [self.axis_key(&self.rows, state), self.axis_key(&self.columns, state)];
+        self.rows.table.value(memo[0]) + self.columns.table.value(memo[1])
+    }
+
+    /// Only the moved tile's count and the blank's line change, along the axis the move crossed
+    fn update(&self, parent: &PackedState, child: &PackedState, estimate: u32, memo: &mut Memo) -> u32 {
+        let (code, from, to) = moved_tile(parent, child);
+        let goal = self.goal(from, code as u8).expect("moved tile takes part");
+
+        let mut estimate = estimate;
+        for (axis, key) in [&self.rows, &self.columns].into_iter().zip(memo.iter_mut()) {
+            let (from, to, goal) = (axis.line[from], axis.line[to], axis.line[goal]);
+            if from != to {
+                let moved = axis.table.step(*key, from, to, goal);
+                estimate = estimate + axis.table.value(moved) - axis.table.value(*key);
+                *key = moved;
+            }
+        }
+
+        estimate
+    }
+}
+
+impl Axis {
+    /// `step` is the distance between a cell and the one beside it in the next line
+    fn new(solved_puzzle: &Puzzle, walls: bool, line: Vec<u8>, lines: usize, step: usize) -> Self {
+        let open = |idx: usize| !walls || solved_puzzle.tiles[idx].rune != WALL;
+
+        let mut capacity = vec![0; lines];
+        let mut gates = 0;
+        for (idx, &from) in line.iter().enumerate() {
+            if !open(idx) {
+                continue;
+            }
+            capacity[from as usize] += 1;
+
+            if line.get(idx + step) == Some(&(from + 1)) && open(idx + step) {
+                gates |= 1 << from;
+            }
+        }
+
+        let key = TableKey { capacity, blank: line[solved_puzzle.space_idx()], gates };
+        Self { table: shared(key), line }
+    }
+}
+
+impl Table {
+    fn build(key: TableKey) -> Self {
+        let lines = key.capacity.len();
+        let mut goal: Counts = [[0; MAX_LINES]; MAX_LINES];
+        for (line, &capacity) in key.capacity.iter().enumerate() {
+            goal[line][line] = capacity - (line == key.blank as usize) as u8;
+        }
+
+        let mut table = Self { key, distances: FxHashMap::default(), horizon: None };
+        let start = table.encode(&goal, table.key.blank);
+        table.distances.insert(start, 0);
+
+        let mut layer = vec![(goal, table.key.blank)];
+        let mut depth = 0u8;
+
+        while !layer.is_empty() && table.distances.len() < MAX_STATES && depth < u8::MAX - 1 {
+            let mut next_layer = vec![];
+
+            for (counts, blank) in layer {
+                let blank = blank as usize;
+                let neighbours = [blank.checked_sub(1), Some(blank + 1).filter(|&line| line < lines)];
+
+                for from in neighbours.into_iter().flatten() {
+                    if table.key.gates & 1 << from.min(blank) == 0 {
+                        continue;
+                    }
+
+                    // Any tile in the neighbouring line can step into the blank's line
+                    for goal_line in 0..lines {
+                        if counts[from][goal_line] == 0 {
+                            continue;
+                        }
+
+                        let mut next = counts;
+                        next[from][goal_line] -= 1;
+                        next[blank][goal_line] += 1;
+
+                        if let Entry::Vacant(entry) = table.distances.entry(table.encode(&next, from as u8)) {
+                            entry.insert(depth + 1);
+                            next_layer.push((next, from as u8));
+                        }
+                    }
+                }
+            }
+
+            layer = next_layer;
+            depth += 1;
+        }
+
+        // The last layer found holds every state at its depth, deeper ones may be missing
+        table.horizon = (!layer.is_empty()).then_some(depth);
+        table
+    }
+
+    /// Only the top left `(lines - 1)` square is stored, the last column and row follow from
+    /// the line capacities and the number of tiles bound for each line.
+    fn encode(&self, counts: &Counts, blank: u8) -> u128 {
+        let lines = self.key.capacity.len();
+        let mut key = blank as u128;
+
+        for row in counts.iter().take(lines - 1) {
+            for &count in row.iter().take(lines - 1) {
+                key = key << 3 | count as u128;
+            }
+        }
+
+        key
+    }
+
+    fn decode(&self, key: u128) -> (Counts, u8) {
+        let lines = self.key.capacity.len();
+        let stored = (lines - 1) * (lines - 1);
+        let blank = (key >> (3 * stored)) as u8;
+
+        let mut counts: Counts = [[0; MAX_LINES]; MAX_LINES];
+        let mut bound = [0; MAX_LINES];
+        for (line, &capacity) in self.key.capacity.iter().enumerate() {
+            bound[line] = capacity - (line == self.key.blank as usize) as u8;
+        }
+
+        for (line, row) in counts.iter_mut().enumerate().take(lines) {
+            let mut tiles = self.key.capacity[line] - (line == blank as usize) as u8;
+            for goal in 0..lines - 1 {
+                row[goal] = if line < lines - 1 {
+                    (key >> Self::shift(lines, line, goal)) as u8 & 0b111
+                } else {
+                    bound[goal]
+                };
+                tiles -= row[goal];
+                bound[goal] -= row[goal];
+            }
+            row[lines - 1] = tiles;
+        }
+
+        (counts, blank)
+    }
+
+    fn shift(lines: usize, line: usize, goal: usize) -> usize {
+        3 * ((lines - 1) * (lines - 1) - 1 - (line * (lines - 1) + goal))
+    }
+
+    /// The key after a tile bound for `goal` steps from line `from` into the blank's line `to`
+    fn step(&self, key: u128, from: u8, to: u8, goal: u8) -> u128 {
+        let lines = self.key.capacity.len();
+        let stored = (lines - 1) * (lines - 1);
+
+        let mut key = key & !(0b111 << (3 * stored)) | (from as u128) << (3 * stored);
+        if (goal as usize) < lines - 1 {
+            if (from as usize) < lines - 1 {
+                key -= 1 << Self::shift(lines, from as usize, goal as usize);
+            }
+            if (to as usize) < lines - 1 {
+                key += 1 << Self::shift(lines, to as usize, goal as usize);
+            }
+        }
+
+        key
+    }
+
+    fn value(&self, key: u128) -> u32 {
+        if let Some(&distance) = self.distances.get(&key) {
+            return distance as u32;
+        }
+
+        // Past the end of the table every tile still has to walk to its goal line
+        let (counts, _) = self.decode(key);
+        let lines = self.key.capacity.len();
+        let walk: u32 = (0..lines)
+            .flat_map(|line| (0..lines).map(move |goal| (line, goal)))
+            .map(|(line, goal)| counts[line][goal] as u32 * line.abs_diff(goal) as u32)
+            .sum();
+
+        walk.max(self.horizon.map_or(0, |horizon| horizon as u32 + 1))
+    }
+}
+
+/// The table for this key, built on first use. Other threads asking for the same key wait for
+/// it instead of building their own.
+fn shared(key: TableKey) -> Arc<Table> {
+    let cell = TABLES.lock().unwrap()
+        .get_or_insert_with(FxHashMap::default)
+        .entry(key.clone())
+        .or_default()
+        .clone();
+
+    cell.get_or_init(|| Arc::new(Table::build(key))).clone()
+}